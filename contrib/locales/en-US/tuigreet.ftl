@@ -15,9 +15,30 @@ select_user = Press Enter to select a user or start typing...
 username = Username:
 wait = Please wait...
 failed = Authentication failed, please try again.
+empty_password = Please type your password before submitting.
+select_session_first = Please choose a session with {$key} before logging in.
+last_login = Last login: {$time}
+unknown_user = Unknown user, please try again.
+maintenance_session_denied = This session is restricted, please choose another one.
+reconnecting = Connection to greetd lost, reconnecting...
+reconnected = Connection to greetd restored, please log in again.
 
 new_command = New command:
 
+menu_more = … and { $count } more
+users_show_all = Show all users…
+users_guest = Guest
+
+time_just_now = just now
+time_minutes_ago = { $count }m ago
+time_hours_ago = { $count }h ago
+time_days_ago = { $count }d ago
+
+issue_user_count = { $count ->
+  [one] { $count } user
+  *[other] { $count } users
+}
+
 shutdown = Shut down
 reboot = Reboot
 
@@ -27,4 +48,15 @@ command_failed = Command failed
 
 status_command = CMD
 status_session = SESS
+status_session_count = { $count ->
+  [one] 1 session
+  *[other] { $count } sessions
+}
 status_caps = CAPS LOCK
+status_diskfree = { $mountpoint }: { $size } free
+status_online = Online
+status_offline = Offline
+status_active_sessions = { $count ->
+  [one] 1 active session
+  *[other] { $count } active sessions
+}
@@ -15,9 +15,30 @@ select_user = Appuyez sur Entrée pour choisir un utilisateur ou tapez son nom..
 username = Nom d'utilisateur :
 wait = Veuillez patienter...
 failed = Erreur d'authentification, veuillez réessayer.
+empty_password = Veuillez saisir votre mot de passe avant de valider.
+select_session_first = Veuillez choisir une session avec {$key} avant de vous connecter.
+last_login = Dernière connexion : {$time}
+unknown_user = Utilisateur inconnu, veuillez réessayer.
+maintenance_session_denied = Cette session est réservée, veuillez en choisir une autre.
+reconnecting = Connexion à greetd perdue, reconnexion en cours...
+reconnected = Connexion à greetd rétablie, veuillez vous reconnecter.
 
 command = Nouvelle commande :
 
+menu_more = … et { $count } de plus
+users_show_all = Afficher tous les utilisateurs…
+users_guest = Invité
+
+time_just_now = à l'instant
+time_minutes_ago = il y a { $count } min
+time_hours_ago = il y a { $count } h
+time_days_ago = il y a { $count } j
+
+issue_user_count = { $count ->
+  [one] { $count } utilisateur
+  *[other] { $count } utilisateurs
+}
+
 shutdown = Éteindre
 reboot = Redémarrer
 
@@ -26,4 +47,15 @@ command_exited = La commande a retourné
 command_failed = Échec de la commande
 
 status_command = CMD
+status_session_count = { $count ->
+  [one] 1 session
+  *[other] { $count } sessions
+}
 status_caps = VERR. MAJ.
+status_diskfree = { $mountpoint } : { $size } libres
+status_online = En ligne
+status_offline = Hors ligne
+status_active_sessions = { $count ->
+  [one] 1 session active
+  *[other] { $count } sessions actives
+}
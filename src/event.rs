@@ -18,6 +18,7 @@ pub enum Event {
   Key(KeyEvent),
   Render,
   PowerCommand(Command),
+  UsernameValidated(String, bool),
   Exit(AuthStatus),
 }
 
@@ -1,4 +1,5 @@
 use std::{
+  borrow::Cow,
   convert::TryInto,
   env,
   error::Error,
@@ -7,36 +8,43 @@ use std::{
   path::PathBuf,
   process,
   sync::Arc,
+  time::{Duration, Instant},
 };
 
 use chrono::{
   format::{Item, StrftimeItems},
   Locale,
 };
+use crossterm::event::KeyModifiers;
 use getopts::{Matches, Options};
 use i18n_embed::DesktopLanguageRequester;
-use tokio::{
-  net::UnixStream,
-  sync::{mpsc::Sender, RwLock, RwLockWriteGuard},
-};
+use tokio::sync::{mpsc::Sender, RwLock, RwLockWriteGuard};
 use tracing_appender::non_blocking::WorkerGuard;
 use zeroize::Zeroize;
 
 use crate::{
   event::Event,
-  info::{get_issue, get_last_command, get_last_session_path, get_last_user_command, get_last_user_name, get_last_user_session, get_last_user_username, get_min_max_uids, get_sessions, get_users},
+  info::{
+    get_active_sessions, get_issue, get_last_command, get_last_login, get_last_session_path, get_last_user_command, get_last_user_name, get_last_user_session, get_last_user_username,
+    get_min_max_uids, get_recent_users, get_sessions, get_users, Throttle, DISKFREE_POLL_INTERVAL, NETWORK_POLL_INTERVAL,
+  },
+  ipc::GreetdStream,
   power::PowerOption,
   ui::{
+    bundled_locales,
     common::{masked::MaskedString, menu::Menu, style::Theme},
     power::Power,
     sessions::{Session, SessionSource, SessionType},
     users::User,
+    MESSAGES,
   },
 };
 
 const DEFAULT_LOG_FILE: &str = "/tmp/tuigreet.log";
 const DEFAULT_LOCALE: Locale = Locale::en_US;
 const DEFAULT_ASTERISKS_CHARS: &str = "*";
+// Options that are accepted but not advertised in the usage message.
+const HIDDEN_OPTIONS: [&str; 1] = ["--password-mask-char-random-seed"];
 // `startx` wants an absolute path to the executable as a first argument.
 // We don't want to resolve the session command in the greeter though, so it should be additionally wrapped with a known noop command (like `/usr/bin/env`).
 const DEFAULT_XSESSION_WRAPPER: &str = "startx /usr/bin/env";
@@ -79,6 +87,8 @@ pub enum SecretDisplay {
   Hidden,
   // All characters are replaced by a placeholder character.
   Character(String),
+  // A fixed-width bar with an indicator moving for every character.
+  Progress,
 }
 
 impl SecretDisplay {
@@ -86,6 +96,7 @@ impl SecretDisplay {
     match self {
       SecretDisplay::Hidden => false,
       SecretDisplay::Character(_) => true,
+      SecretDisplay::Progress => true,
     }
   }
 }
@@ -99,17 +110,57 @@ pub enum GreetAlign {
   Right,
 }
 
+// This enum models when the greeting should change, if several were provided.
+#[derive(SmartDefault, Debug, Copy, Clone, PartialEq)]
+pub enum GreetingRotation {
+  #[default]
+  Minute,
+  Keypress,
+}
+
+// This enum models the order in which the username and answer prompts are
+// displayed.
+#[derive(SmartDefault, Debug, Copy, Clone, PartialEq)]
+pub enum PromptOrder {
+  #[default]
+  UserFirst,
+  PasswordFirst,
+}
+
+// This enum models what should be selected when both a command and a session
+// were remembered for a user.
+#[derive(SmartDefault, Debug, Copy, Clone, PartialEq)]
+pub enum RememberPreference {
+  #[default]
+  Session,
+  Command,
+}
+
 #[derive(SmartDefault)]
 pub struct Greeter {
   pub debug: bool,
+  // Whether memory should be left as is when scrubbing, to inspect the state
+  // of the greeter. Only available in debug builds.
+  #[cfg(debug_assertions)]
+  pub no_scrub: bool,
   pub logfile: String,
   pub logger: Option<WorkerGuard>,
+  // File to which the outcome of authentication attempts is appended.
+  pub metrics_file: Option<PathBuf>,
+  // When the current authentication attempt started.
+  pub auth_started: Option<Instant>,
+  // File to which the sessions started are appended.
+  pub audit_file: Option<PathBuf>,
+  // Command and type of the session being started, until greetd acknowledges it.
+  pub session_starting: Option<(String, SessionType)>,
 
   #[default(DEFAULT_LOCALE)]
   pub locale: Locale,
   pub config: Option<Matches>,
   pub socket: String,
-  pub stream: Option<Arc<RwLock<UnixStream>>>,
+  pub stream: Option<Arc<RwLock<GreetdStream>>>,
+  // Whether the session was recreated after greetd reported a conflicting one.
+  pub session_recreated: bool,
   pub events: Option<Sender<Event>>,
 
   // Current mode of the application, will define what actions are permitted.
@@ -129,26 +180,90 @@ pub struct Greeter {
   pub session_source: SessionSource,
   // List of session files found on disk.
   pub session_paths: Vec<(PathBuf, SessionType)>,
+  // Whether session directories should be read recursively.
+  pub sessions_recursive: bool,
+  // Whether to reload the sessions when session files change.
+  pub watch_sessions: bool,
+  // Whether a session must be explicitly chosen before logging in.
+  pub require_session_selection: bool,
+  // Whether the user explicitly chose a session or command.
+  pub session_chosen: bool,
   // Menu for session selection.
   pub sessions: Menu<Session>,
+  // Title of the session menu, instead of the translated one.
+  pub sessions_title: Option<String>,
+  // Maximum number of sessions displayed at once in the session menu.
+  pub max_sessions_displayed: Option<usize>,
+  // Whether to display the number of available sessions in the status bar.
+  pub show_session_count: bool,
+  // Whether to display the available sessions as a row of tabs.
+  pub sessions_tabs: bool,
+  // Whether to display when each session was last started in the session menu.
+  pub show_session_last_used: bool,
+  // Number of sessions opened by other users, if they should be displayed.
+  pub active_sessions: Option<usize>,
+  // Desktop names to use for sessions, by slug, instead of their `DesktopNames`.
+  pub session_desktop_names: Vec<(String, String)>,
   // Wrapper command to prepend to non-X11 sessions.
   pub session_wrapper: Option<String>,
   // Wrapper command to prepend to X11 sessions.
   pub xsession_wrapper: Option<String>,
+  // Activation token to hand over to the started session.
+  pub activation_token: Option<String>,
+  // Environment variables never passed to the started session.
+  pub strip_env: Vec<String>,
+  // Desktop environment name matched against `NotShowIn` in session files.
+  pub xdg_env_name: Option<String>,
+  // Slug of the session restricted to maintenance users.
+  pub maintenance_session: Option<String>,
+  // Users allowed to start the maintenance session.
+  pub maintenance_session_users: Vec<String>,
+  // Whether X11 sessions should be hidden when Xorg is not installed.
+  pub hide_x11_if_unavailable: bool,
 
   // Whether user menu is enabled.
   pub user_menu: bool,
   // Menu for user selection.
   pub users: Menu<User>,
+  // Full list of users, while only the most recent ones are displayed.
+  pub all_users: Option<Vec<User>>,
+  // User logging in without a password, offered first in the user menu.
+  pub guest_user: Option<String>,
+  // Command started for the guest user, regardless of the chosen session.
+  pub guest_command: Option<String>,
+  // Title of the user menu, instead of the translated one.
+  pub users_title: Option<String>,
+  // Template for the users in the user menu, with `%n` and `%u` placeholders.
+  pub user_menu_format: Option<String>,
   // Current username. Masked to display the full name if available.
   pub username: MaskedString,
+  // Whether the username should be displayed next to the full name.
+  pub show_username_and_name: bool,
   // Prompt that should be displayed to ask for entry.
   pub prompt: Option<String>,
 
+  // Order in which the username and answer prompts are displayed.
+  pub prompt_order: PromptOrder,
+
   // Whether the current edition prompt should be hidden.
   pub asking_for_secret: bool,
   // How should secrets be displayed?
   pub secret_display: SecretDisplay,
+  // Seed used to pick characters when secrets are redacted with several ones.
+  pub secret_mask_seed: u64,
+  // Whether an empty secret can be submitted.
+  #[default(true)]
+  pub allow_empty_password: bool,
+  // Whether Tab should submit the answer to a prompt, like Enter.
+  pub tab_submits_password: bool,
+  // Whether the password should be restored after a failed attempt.
+  pub keep_password_on_failure: bool,
+  // Whether to exit after the first failed authentication attempt.
+  pub once: bool,
+  // Message displayed below the failure message, to guide the user.
+  pub retry_message: Option<String>,
+  // Last submitted secret, kept until we know whether it was accepted.
+  pub previous_secret: Option<String>,
 
   // Whether last logged-in user should be remembered.
   pub remember: bool,
@@ -156,22 +271,95 @@ pub struct Greeter {
   pub remember_session: bool,
   // Whether last launched session for the current user should be remembered.
   pub remember_user_session: bool,
+  // What to select when both a command and a session were remembered.
+  pub remember_user_session_prefer: RememberPreference,
+  // Users for whom nothing should be remembered.
+  pub remember_exclude_users: Vec<String>,
+  // Whether to start authenticating the remembered user right away.
+  #[default(true)]
+  pub auto_create_session: bool,
 
   // Style object for the terminal UI
   pub theme: Theme,
+  // Whether the cursor should be forced not to blink.
+  pub steady_cursor: bool,
+  // Whether to dim the screen for a frame when switching screens.
+  pub transition_effect: bool,
+  // Screen drawn on the last frame, used to detect screen switches.
+  pub drawn_mode: Option<Mode>,
+  // Display the caps lock indicator
+  #[default(true)]
+  pub capslock: bool,
   // Display the current time
   pub time: bool,
+  // Mountpoint for which free space should be displayed.
+  pub diskfree_mountpoint: Option<PathBuf>,
+  // Last known free space on the configured mountpoint.
+  #[default(Throttle::new(DISKFREE_POLL_INTERVAL))]
+  pub diskfree: Throttle<Option<u64>>,
+  // Whether to display if the machine is connected to a network.
+  pub show_network: bool,
+  // Last known network status.
+  #[default(Throttle::new(NETWORK_POLL_INTERVAL))]
+  pub network: Throttle<bool>,
   // Time format
   pub time_format: Option<String>,
+  // Whether the default time format comes from the locale data instead of the
+  // translation files.
+  pub locale_time_format: bool,
   // Greeting message (MOTD) to use to welcome the user.
   pub greeting: Option<String>,
+  // Whether blank lines and spaces around the greeting should be removed.
+  #[default(true)]
+  pub greeting_trim: bool,
+  // Whether the greeting spans the whole screen, above the main window.
+  pub greeting_full_width: bool,
+  // All greeting messages to rotate through.
+  pub greetings: Vec<String>,
+  // Index of the currently displayed greeting.
+  pub greeting_index: usize,
+  // When to switch to the next greeting.
+  pub greeting_rotate: GreetingRotation,
+  // Time at which the current greeting was first displayed.
+  pub greeting_time: Option<Instant>,
+  // Command run to check that a username exists before authenticating.
+  pub username_validator: Option<String>,
+  // Whether to display the last login time of the user being authenticated.
+  pub show_last_login: bool,
+  // Text displayed in the status bar, before the action hints.
+  pub footer: Option<String>,
+  // Text displayed between groups of the status bar.
+  pub status_separator: Option<String>,
+  // Message displayed while the session is starting.
+  pub session_starting_message: Option<String>,
+  // Replacements for the messages from greetd containing a given text.
+  pub prompt_overrides: Vec<(String, String)>,
+  // Message displayed while waiting for greetd to answer.
+  pub working_text: Option<String>,
   // Transaction message to show to the user.
   pub message: Option<String>,
+  // Time at which the current message was set.
+  pub message_time: Option<Instant>,
+  // Whether the current message reports an error.
+  pub message_error: bool,
+  // Duration after which messages are cleared, if they should be.
+  pub message_timeout: Option<Duration>,
+  // Duration after which error messages are cleared, instead of the above.
+  pub error_message_timeout: Option<Duration>,
+  // Minimum duration a failure message should stay displayed before user input
+  // can clear it.
+  pub password_feedback_delay: Duration,
 
   // Menu for power options.
   pub powers: Menu<Power>,
+  // Title of the power menu, instead of the translated one.
+  pub power_title: Option<String>,
   // Whether to prefix the power commands with `setsid`.
   pub power_setsid: bool,
+  // Whether to display the command run by each power option.
+  pub show_power_commands: bool,
+  // Whether to keep the power menu open after a power command failed.
+  pub power_stay_on_failure: bool,
 
   #[default(2)]
   pub kb_command: u8,
@@ -179,6 +367,11 @@ pub struct Greeter {
   pub kb_sessions: u8,
   #[default(12)]
   pub kb_power: u8,
+  // Whether j, k, g and G navigate in menus.
+  pub vi_keys: bool,
+  // Modifiers that must be held with Enter to submit text entries.
+  #[default(KeyModifiers::NONE)]
+  pub submit_modifiers: KeyModifiers,
 
   // The software is waiting for a response from `greetd`.
   pub working: bool,
@@ -190,7 +383,7 @@ pub struct Greeter {
 
 impl Drop for Greeter {
   fn drop(&mut self) {
-    self.scrub(true, false);
+    self.teardown();
   }
 }
 
@@ -205,16 +398,13 @@ impl Greeter {
       title: fl!("title_power"),
       options: Default::default(),
       selected: 0,
+      expand: None,
     };
 
     #[cfg(not(test))]
     {
-      match env::var("GREETD_SOCK") {
-        Ok(socket) => greeter.socket = socket,
-        Err(_) => {
-          eprintln!("GREETD_SOCK must be defined");
-          process::exit(1);
-        }
+      if let Ok(socket) = env::var("GREETD_SOCK") {
+        greeter.socket = socket;
       }
 
       let args = env::args().collect::<Vec<String>>();
@@ -226,6 +416,11 @@ impl Greeter {
         process::exit(1);
       }
 
+      if greeter.socket.is_empty() {
+        eprintln!("GREETD_SOCK or --greetd-socket must be defined");
+        process::exit(1);
+      }
+
       greeter.connect().await;
     }
 
@@ -240,11 +435,14 @@ impl Greeter {
     }
 
     greeter.sessions = Menu {
-      title: fl!("title_session"),
+      title: greeter.sessions_title.clone().unwrap_or_else(|| fl!("title_session")),
       options: sessions,
       selected: 0,
+      expand: None,
     };
 
+    greeter.preselect_session(|name| env::var(name).ok());
+
     // If we should remember the last logged-in user.
     if greeter.remember {
       if let Some(username) = get_last_user_username() {
@@ -252,19 +450,10 @@ impl Greeter {
 
         // If, on top of that, we should remember their last session.
         if greeter.remember_user_session {
-          // See if we have the last free-form command from the user.
-          if let Ok(command) = get_last_user_command(greeter.username.get()) {
-            greeter.session_source = SessionSource::Command(command);
-          }
+          let command = get_last_user_command(greeter.username.get()).ok();
+          let session = get_last_user_session(greeter.username.get()).ok();
 
-          // If a session was saved, use it and its name.
-          if let Ok(ref session_path) = get_last_user_session(greeter.username.get()) {
-            // Set the selected menu option and the session source.
-            if let Some(index) = greeter.sessions.options.iter().position(|Session { path, .. }| path.as_deref() == Some(session_path)) {
-              greeter.sessions.selected = index;
-              greeter.session_source = SessionSource::Session(greeter.sessions.selected);
-            }
-          }
+          greeter.restore_user_session_source(command, session);
         }
       }
     }
@@ -288,11 +477,21 @@ impl Greeter {
 
   // Scrub memory of all data, unless `soft` is true, in which case, we will
   // keep the username (can happen if a wrong password was entered, we want to
-  // give the user another chance, as PAM would).
+  // give the user another chance, as PAM would). If requested, the password
+  // that was just refused is also put back in the buffer so it can be fixed.
   fn scrub(&mut self, scrub_message: bool, soft: bool) {
     self.buffer.zeroize();
     self.prompt.zeroize();
 
+    if soft && self.keep_password_on_failure {
+      if let Some(secret) = self.previous_secret.take() {
+        self.buffer = secret;
+        self.cursor_offset = 0;
+      }
+    }
+
+    self.previous_secret.zeroize();
+
     if !soft {
       self.username.zeroize();
     }
@@ -302,8 +501,26 @@ impl Greeter {
     }
   }
 
+  // Scrubs memory before the greeter goes away, unless this was disabled to
+  // inspect its final state in a debug build.
+  fn teardown(&mut self) {
+    #[cfg(debug_assertions)]
+    if self.no_scrub {
+      return;
+    }
+
+    self.scrub(true, false);
+  }
+
   // Reset the software to its initial state.
   pub async fn reset(&mut self, soft: bool) {
+    self.rewind(soft);
+    self.connect().await;
+  }
+
+  // Puts the software back to its initial state, without opening a new
+  // connection to `greetd`.
+  pub fn rewind(&mut self, soft: bool) {
     if soft {
       self.mode = Mode::Password;
       self.previous_mode = Mode::Password;
@@ -316,12 +533,11 @@ impl Greeter {
     self.done = false;
 
     self.scrub(false, soft);
-    self.connect().await;
   }
 
   // Connect to `greetd` and return a stream we can safely write to.
   pub async fn connect(&mut self) {
-    match UnixStream::connect(&self.socket).await {
+    match GreetdStream::connect(&self.socket).await {
       Ok(stream) => self.stream = Some(Arc::new(RwLock::new(stream))),
 
       Err(err) => {
@@ -335,7 +551,7 @@ impl Greeter {
     self.config.as_ref().unwrap()
   }
 
-  pub async fn stream(&self) -> RwLockWriteGuard<'_, UnixStream> {
+  pub async fn stream(&self) -> RwLockWriteGuard<'_, GreetdStream> {
     self.stream.as_ref().unwrap().write().await
   }
 
@@ -362,6 +578,12 @@ impl Greeter {
     80
   }
 
+  // Returns the maximum width of the band of the screen in which everything is
+  // drawn, if it should not span the whole terminal.
+  pub fn max_content_width(&self) -> Option<u16> {
+    self.option("max-content-width").and_then(|value| value.parse::<u16>().ok()).filter(|width| *width > 0)
+  }
+
   // Returns the padding of the screen from the provided arguments.
   pub fn window_padding(&self) -> u16 {
     if let Some(value) = self.option("window-padding") {
@@ -396,6 +618,17 @@ impl Greeter {
     1
   }
 
+  // Returns the spacing between the greeting and the prompts.
+  pub fn greeting_prompt_spacing(&self) -> u16 {
+    if let Some(value) = self.option("greeting-prompt-spacing") {
+      if let Ok(spacing) = value.parse::<u16>() {
+        return spacing;
+      }
+    }
+
+    1
+  }
+
   pub fn greet_align(&self) -> GreetAlign {
     if let Some(value) = self.option("greet-align") {
       match value.as_str() {
@@ -408,6 +641,18 @@ impl Greeter {
     }
   }
 
+  pub fn time_align(&self) -> GreetAlign {
+    if let Some(value) = self.option("time-align") {
+      match value.as_str() {
+        "left" => GreetAlign::Left,
+        "right" => GreetAlign::Right,
+        _ => GreetAlign::Center,
+      }
+    } else {
+      GreetAlign::default()
+    }
+  }
+
   // Sets the locale that will be used for this invocation from environment.
   fn set_locale(&mut self) {
     let locale = DesktopLanguageRequester::requested_languages()
@@ -428,45 +673,135 @@ impl Greeter {
 
     opts.optflag("h", "help", "show this usage information");
     opts.optflag("v", "version", "print version information");
+    opts.optflag("", "list-locales", "print the available locales and exit");
+
+    #[cfg(debug_assertions)]
+    opts.optflag("", "no-scrub", "do not zeroize memory on exit, for debugging");
     opts.optflagopt("d", "debug", "enable debug logging to the provided file, or to /tmp/tuigreet.log", "FILE");
+    opts.optopt("", "metrics-file", "append the outcome of authentication attempts to a file", "FILE");
+    opts.optopt("", "audit-file", "append the sessions started to a file", "FILE");
+    opts.optopt("", "greetd-socket", "address of the greetd socket, overriding GREETD_SOCK", "PATH|@NAME|tcp://HOST:PORT");
     opts.optopt("c", "cmd", "command to run", "COMMAND");
+    opts.optflag("", "expand-cmd-env", "expand ${VAR} references to environment variables in the default command");
     opts.optmulti("", "env", "environment variables to run the default session with (can appear more than once)", "KEY=VALUE");
     opts.optopt("s", "sessions", "colon-separated list of Wayland session paths", "DIRS");
     opts.optopt("", "session-wrapper", "wrapper command to initialize the non-X11 session", "'CMD [ARGS]...'");
     opts.optopt("x", "xsessions", "colon-separated list of X11 session paths", "DIRS");
+    opts.optflag("", "require-session-selection", "require a session to be chosen before logging in");
+    opts.optflag("", "sessions-recursive", "also look for session files in subdirectories of session paths");
+    opts.optflag("", "watch-sessions", "reload the sessions when session files are changed");
+    opts.optopt("", "xdg-env-name", "hide sessions listing this name in their NotShowIn key", "NAME");
+    opts.optmulti(
+      "",
+      "session-desktop-names",
+      "desktop names of the session with the provided slug, instead of its DesktopNames (can appear more than once)",
+      "SLUG=NAMES",
+    );
+    opts.optopt("", "maintenance-session", "session file name, without extension, restricted to maintenance users", "SLUG");
+    opts.optopt("", "maintenance-session-users", "comma-separated list of users allowed to start the maintenance session", "USERS");
     opts.optopt("", "xsession-wrapper", xsession_wrapper_desc.as_str(), "'CMD [ARGS]...'");
     opts.optflag("", "no-xsession-wrapper", "do not wrap commands for X11 sessions");
+    opts.optflag("", "pass-activation-token", "pass XDG_ACTIVATION_TOKEN through to the started session");
+    opts.optopt("", "strip-env", "comma-separated list of environment variables never passed to the session", "VARS");
+    opts.optflag("", "hide-x11-if-unavailable", "hide X11 sessions if Xorg cannot be found");
+    opts.optflag("", "show-session-count", "display the number of available sessions in the status bar");
+    opts.optflag("", "sessions-tabs", "display the available sessions as tabs at the top of the screen");
+    opts.optflag("", "show-session-last-used", "display when each session was last started in the session menu");
+    opts.optopt("", "max-sessions-displayed", "maximum number of sessions shown at once in the session menu", "N");
+    opts.optopt("", "sessions-title", "title of the session menu", "TITLE");
+    opts.optflag("", "show-active-sessions", "display the number of sessions opened by other users");
     opts.optopt("w", "width", "width of the main prompt (default: 80)", "WIDTH");
+    opts.optopt("", "max-content-width", "maximum width of the screen used for the status bar and clock", "WIDTH");
     opts.optflag("i", "issue", "show the host's issue file");
-    opts.optopt("g", "greeting", "show custom text above login prompt", "GREETING");
+    opts.optmulti("g", "greeting", "show custom text above login prompt (can be repeated)", "GREETING");
+    opts.optopt("", "greeting-rotate", "when to switch between several greetings (default: 'minute')", "[minute|keypress]");
+    opts.optflag("", "greeting-no-trim", "keep leading and trailing whitespace of the greeting");
+    opts.optflag("", "greeting-full-width", "display the greeting across the whole screen, above the prompt");
+    opts.optopt("", "footer", "text shown in the status bar, %h being the hostname and %v the version", "TEXT");
+    opts.optopt("", "status-separator", "text shown between groups of the status bar (default: ' ')", "TEXT");
+    opts.optopt("", "session-starting-message", "text shown while the session starts, %s being the session name", "TEXT");
+    opts.optopt("", "working-text", "text shown while waiting for an answer from greetd", "TEXT");
+    opts.optmulti(
+      "",
+      "prompt-override",
+      "text shown instead of messages from greetd containing MATCH (can appear more than once)",
+      "MATCH=TEXT",
+    );
     opts.optflag("t", "time", "display the current date and time");
     opts.optopt("", "time-format", "custom strftime format for displaying date and time", "FORMAT");
+    opts.optflag("", "locale-time-format", "use the date and time format of the current locale");
+    opts.optopt("", "time-align", "alignment of the date and time at the top of the screen (default: 'center')", "[left|center|right]");
+    opts.optflag("", "no-capslock", "do not display the caps lock indicator");
+    opts.optopt("", "show-diskfree", "display the free space available on a mountpoint", "MOUNTPOINT");
+    opts.optflag("", "show-network", "display whether the machine is connected to a network");
     opts.optflag("r", "remember", "remember last logged-in username");
     opts.optflag("", "remember-session", "remember last selected session");
     opts.optflag("", "remember-user-session", "remember last selected session for each user");
+    opts.optopt(
+      "",
+      "remember-user-session-prefer",
+      "what to select when both a command and a session were remembered (default: session)",
+      "[command|session]",
+    );
+    opts.optflag("", "no-auto-create-session", "wait for Enter before authenticating the remembered user");
+    opts.optopt("", "remember-exclude-user", "comma-separated list of users for whom nothing is remembered", "USERS");
+    opts.optflag("", "show-username-and-name", "display the username next to the full name of the selected user");
+    opts.optflag("", "show-last-login", "display when the user last logged in");
+    opts.optopt(
+      "",
+      "username-validator",
+      "command checking that a username exists, receiving it as its last argument",
+      "'CMD [ARGS]...'",
+    );
     opts.optflag("", "user-menu", "allow graphical selection of users from a menu");
+    opts.optopt("", "user-menu-format", "how users with a full name are displayed in the menu (default: '%n (%u)')", "TEMPLATE");
+    opts.optopt("", "guest-user", "user allowed to log in without a password", "USER");
+    opts.optopt("", "guest-command", "command to run for the guest user", "CMD");
     opts.optopt("", "user-menu-min-uid", "minimum UID to display in the user selection menu", "UID");
     opts.optopt("", "user-menu-max-uid", "maximum UID to display in the user selection menu", "UID");
+    opts.optopt("", "users-title", "title of the user menu", "TITLE");
+    opts.optflag("", "user-menu-require-users", "fail if the user selection menu would be empty");
+    opts.optopt("", "user-menu-recent", "only display the N most recently active users in the menu at first", "N");
     opts.optopt("", "theme", "define the application theme colors", "THEME");
+    opts.optflag("", "cursor-blink-off", "force a steady, non-blinking cursor");
+    opts.optflag("", "transition-effect", "briefly dim the screen when switching between screens");
     opts.optflag("", "asterisks", "display asterisks when a secret is typed");
+    opts.optflag("", "secret-progress", "display a moving indicator when a secret is typed");
     opts.optopt("", "asterisks-char", "characters to be used to redact secrets (default: *)", "CHARS");
+    opts.optopt("", "password-mask-char-random-seed", "", "SEED");
+    opts.optflag("", "no-empty-password", "prevent submitting an empty password");
+    opts.optflag("", "tab-submits-password", "submit the password with Tab as well as Enter");
+    opts.optflag("", "keep-password-on-failure", "keep the entered password after a failed attempt");
+    opts.optflag("", "once", "exit after the first failed authentication attempt");
+    opts.optopt("", "retry-message", "text displayed after a failed authentication attempt", "TEXT");
+    opts.optopt("", "password-feedback-delay", "minimum time an authentication failure is displayed (default: 0)", "MILLISECONDS");
+    opts.optopt("", "message-timeout", "clear messages after they were displayed for a while", "SECONDS");
+    opts.optopt("", "error-message-timeout", "clear error messages after they were displayed for a while", "SECONDS");
     opts.optopt("", "window-padding", "padding inside the terminal area (default: 0)", "PADDING");
     opts.optopt("", "container-padding", "padding inside the main prompt container (default: 1)", "PADDING");
     opts.optopt("", "prompt-padding", "padding between prompt rows (default: 1)", "PADDING");
+    opts.optopt("", "greeting-prompt-spacing", "spacing between the greeting and the prompts (default: 1)", "ROWS");
     opts.optopt(
       "",
       "greet-align",
       "alignment of the greeting text in the main prompt container (default: 'center')",
       "[left|center|right]",
     );
+    opts.optopt("", "prompt-order", "order of the username and password prompts (default: 'user-first')", "[user-first|password-first]");
 
     opts.optopt("", "power-shutdown", "command to run to shut down the system", "'CMD [ARGS]...'");
     opts.optopt("", "power-reboot", "command to run to reboot the system", "'CMD [ARGS]...'");
     opts.optflag("", "power-no-setsid", "do not prefix power commands with setsid");
+    opts.optflag("", "show-power-commands", "display the command run by each power option");
+    opts.optopt("", "power-title", "title of the power menu", "TITLE");
+    opts.optopt("", "power-options", "comma-separated list of power options to display, in order", "shutdown,reboot");
+    opts.optflag("", "power-stay-on-failure", "keep the power menu open when a power command fails");
 
     opts.optopt("", "kb-command", "F-key to use to open the command menu", "[1-12]");
     opts.optopt("", "kb-sessions", "F-key to use to open the sessions menu", "[1-12]");
     opts.optopt("", "kb-power", "F-key to use to open the power menu", "[1-12]");
+    opts.optflag("", "vi-keys", "navigate in menus with j, k, g and G");
+    opts.optopt("", "submit-key", "key combination submitting text entries", "[enter|alt+enter]");
 
     opts
   }
@@ -491,6 +826,15 @@ impl Greeter {
       print_version();
       process::exit(0);
     }
+    if self.config().opt_present("list-locales") {
+      print_locales();
+      process::exit(0);
+    }
+
+    #[cfg(debug_assertions)]
+    {
+      self.no_scrub = self.config().opt_present("no-scrub");
+    }
 
     if self.config().opt_present("debug") {
       self.debug = true;
@@ -501,6 +845,9 @@ impl Greeter {
       }
     }
 
+    self.metrics_file = self.option("metrics-file").map(PathBuf::from);
+    self.audit_file = self.option("audit-file").map(PathBuf::from);
+
     if self.config().opt_present("issue") && self.config().opt_present("greeting") {
       return Err("Only one of --issue and --greeting may be used at the same time".into());
     }
@@ -511,6 +858,17 @@ impl Greeter {
       }
     }
 
+    self.steady_cursor = self.config().opt_present("cursor-blink-off");
+    self.transition_effect = self.config().opt_present("transition-effect");
+
+    if self.config().opt_present("asterisks") && self.config().opt_present("secret-progress") {
+      return Err("Only one of --asterisks and --secret-progress may be used at the same time".into());
+    }
+
+    if self.config().opt_present("secret-progress") {
+      self.secret_display = SecretDisplay::Progress;
+    }
+
     if self.config().opt_present("asterisks") {
       let asterisk = if let Some(value) = self.config().opt_str("asterisks-char") {
         if value.chars().count() < 1 {
@@ -525,7 +883,54 @@ impl Greeter {
       self.secret_display = SecretDisplay::Character(asterisk);
     }
 
+    if let Some(seed) = self.config().opt_str("password-mask-char-random-seed") {
+      self.secret_mask_seed = match seed.parse::<u64>() {
+        Ok(seed) => seed,
+        Err(_) => return Err("--password-mask-char-random-seed must be a positive integer".into()),
+      };
+    }
+
+    self.allow_empty_password = !self.config().opt_present("no-empty-password");
+    self.tab_submits_password = self.config().opt_present("tab-submits-password");
+    self.keep_password_on_failure = self.config().opt_present("keep-password-on-failure");
+    self.once = self.config().opt_present("once");
+    self.retry_message = self.option("retry-message").filter(|message| !message.is_empty());
+
+    if let Some(order) = self.option("prompt-order") {
+      self.prompt_order = match order.as_str() {
+        "user-first" => PromptOrder::UserFirst,
+        "password-first" => PromptOrder::PasswordFirst,
+        _ => return Err("--prompt-order must be one of 'user-first' or 'password-first'".into()),
+      };
+    }
+
+    if let Some(delay) = self.config().opt_str("password-feedback-delay") {
+      match delay.parse::<u64>() {
+        Ok(delay) => self.password_feedback_delay = Duration::from_millis(delay),
+        Err(_) => return Err("--password-feedback-delay must be a number of milliseconds".into()),
+      }
+    }
+
+    if let Some(timeout) = self.option("message-timeout") {
+      match timeout.parse::<u64>() {
+        Ok(timeout) if timeout > 0 => self.message_timeout = Some(Duration::from_secs(timeout)),
+        _ => return Err("--message-timeout must be a positive number of seconds".into()),
+      }
+    }
+
+    if let Some(timeout) = self.option("error-message-timeout") {
+      match timeout.parse::<u64>() {
+        Ok(timeout) if timeout > 0 => self.error_message_timeout = Some(Duration::from_secs(timeout)),
+        _ => return Err("--error-message-timeout must be a positive number of seconds".into()),
+      }
+    }
+
     self.time = self.config().opt_present("time");
+    self.capslock = !self.config().opt_present("no-capslock");
+    self.greeting_trim = !self.config().opt_present("greeting-no-trim");
+    self.greeting_full_width = self.config().opt_present("greeting-full-width");
+    self.diskfree_mountpoint = self.option("show-diskfree").map(PathBuf::from);
+    self.show_network = self.config().opt_present("show-network");
 
     if let Some(format) = self.config().opt_str("time-format") {
       if StrftimeItems::new(&format).any(|item| item == Item::Error) {
@@ -535,6 +940,23 @@ impl Greeter {
       self.time_format = Some(format);
     }
 
+    self.locale_time_format = self.config().opt_present("locale-time-format");
+
+    self.show_username_and_name = self.config().opt_present("show-username-and-name");
+    self.show_last_login = self.config().opt_present("show-last-login");
+    self.username_validator = self.option("username-validator");
+    self.sessions_title = self.option("sessions-title");
+    self.users_title = self.option("users-title");
+    self.user_menu_format = self.option("user-menu-format").filter(|format| !format.is_empty());
+    self.power_title = self.option("power-title");
+
+    if self.config().opt_present("guest-command") && !self.config().opt_present("guest-user") {
+      return Err("--guest-command must be used with --guest-user".into());
+    }
+
+    self.guest_user = self.option("guest-user").filter(|user| !user.is_empty());
+    self.guest_command = self.option("guest-command");
+
     if self.config().opt_present("user-menu") {
       self.user_menu = true;
 
@@ -549,12 +971,62 @@ impl Greeter {
       }
 
       self.users = Menu {
-        title: fl!("title_users"),
+        title: self.users_title.clone().unwrap_or_else(|| fl!("title_users")),
         options: get_users(min_uid, max_uid),
         selected: 0,
+        expand: None,
       };
 
       tracing::info!("found {} users", self.users.options.len());
+
+      if let Some(ref guest) = self.guest_user {
+        self.users.options.retain(|user| user.username != *guest);
+      }
+
+      if self.users.options.is_empty() {
+        if self.config().opt_present("user-menu-require-users") {
+          return Err(format!("No users were found with a UID between {min_uid} and {max_uid}").into());
+        }
+
+        if self.guest_user.is_none() {
+          tracing::warn!("no users found for the user menu, falling back to typing the username");
+
+          self.user_menu = false;
+        }
+      }
+
+      if let Some(count) = self.option("user-menu-recent") {
+        let count = match count.parse::<usize>() {
+          Ok(count) if count > 0 => count,
+          _ => return Err("--user-menu-recent must be a positive number".into()),
+        };
+
+        let last_user = match self.config().opt_present("remember") {
+          true => get_last_user_username(),
+          false => None,
+        };
+
+        let recent = get_recent_users(&self.users.options, count, last_user.as_deref(), get_last_login);
+
+        // The full list is kept behind a last entry expanding the menu.
+        if !recent.is_empty() && recent.len() < self.users.options.len() {
+          self.all_users = Some(std::mem::replace(&mut self.users.options, recent));
+          self.users.expand = Some(fl!("users_show_all"));
+        }
+      }
+
+      if let Some(ref guest) = self.guest_user {
+        let entry = User {
+          username: guest.clone(),
+          name: Some(fl!("users_guest")),
+        };
+
+        if let Some(ref mut users) = self.all_users {
+          users.insert(0, entry.clone());
+        }
+
+        self.users.options.insert(0, entry);
+      }
     }
 
     if self.config().opt_present("remember-session") && self.config().opt_present("remember-user-session") {
@@ -567,23 +1039,50 @@ impl Greeter {
     self.remember = self.config().opt_present("remember");
     self.remember_session = self.config().opt_present("remember-session");
     self.remember_user_session = self.config().opt_present("remember-user-session");
-    self.greeting = self.option("greeting");
 
-    // If the `--cmd` argument is provided, it will override the selected session.
-    if let Some(command) = self.option("cmd") {
-      let envs = self.options_multi("env");
+    if let Some(prefer) = self.option("remember-user-session-prefer") {
+      self.remember_user_session_prefer = match prefer.as_str() {
+        "command" => RememberPreference::Command,
+        "session" => RememberPreference::Session,
+        _ => return Err("--remember-user-session-prefer must be one of 'command' or 'session'".into()),
+      };
+    }
 
-      if let Some(envs) = envs {
-        for env in envs {
-          if !env.contains('=') {
-            return Err(format!("malformed environment variable definition for '{env}'").into());
-          }
+    self.auto_create_session = !self.config().opt_present("no-auto-create-session");
+
+    if let Some(users) = self.option("remember-exclude-user") {
+      self.remember_exclude_users = users.split(',').map(str::trim).filter(|user| !user.is_empty()).map(str::to_string).collect();
+    }
+    self.greetings = self.options_multi("greeting").unwrap_or_default();
+    self.greeting = self.greetings.first().cloned();
+
+    if let Some(rotate) = self.option("greeting-rotate") {
+      self.greeting_rotate = match rotate.as_str() {
+        "minute" => GreetingRotation::Minute,
+        "keypress" => GreetingRotation::Keypress,
+        _ => return Err("--greeting-rotate must be one of 'minute' or 'keypress'".into()),
+      };
+    }
+    self.session_starting_message = self.option("session-starting-message");
+    self.working_text = self.option("working-text");
+
+    if let Some(overrides) = self.options_multi("prompt-override") {
+      for value in overrides {
+        match value.split_once('=') {
+          Some((pattern, replacement)) if !pattern.is_empty() => self.prompt_overrides.push((pattern.to_string(), replacement.to_string())),
+          _ => return Err(format!("malformed prompt override definition for '{value}'").into()),
         }
       }
+    }
+    self.footer = self.option("footer");
+    self.status_separator = self.option("status-separator");
 
-      self.session_source = SessionSource::DefaultCommand(command, self.options_multi("env"));
+    if let Some(socket) = self.option("greetd-socket") {
+      self.socket = socket;
     }
 
+    self.parse_default_command(|name| env::var(name).ok())?;
+
     if let Some(dirs) = self.option("sessions") {
       self.session_paths.extend(env::split_paths(&dirs).map(|dir| (dir, SessionType::Wayland)));
     }
@@ -592,6 +1091,24 @@ impl Greeter {
       self.session_paths.extend(env::split_paths(&dirs).map(|dir| (dir, SessionType::X11)));
     }
 
+    self.sessions_recursive = self.config().opt_present("sessions-recursive");
+    self.watch_sessions = self.config().opt_present("watch-sessions");
+    self.require_session_selection = self.config().opt_present("require-session-selection");
+    self.show_session_count = self.config().opt_present("show-session-count");
+    self.sessions_tabs = self.config().opt_present("sessions-tabs");
+    self.show_session_last_used = self.config().opt_present("show-session-last-used");
+
+    if self.config().opt_present("show-active-sessions") {
+      self.active_sessions = get_active_sessions();
+    }
+
+    if let Some(max) = self.config().opt_str("max-sessions-displayed") {
+      match max.parse::<usize>() {
+        Ok(max) if max > 0 => self.max_sessions_displayed = Some(max),
+        _ => return Err("--max-sessions-displayed must be a positive number".into()),
+      }
+    }
+
     if self.option("session-wrapper").is_some() {
       self.session_wrapper = self.option("session-wrapper");
     }
@@ -600,23 +1117,81 @@ impl Greeter {
       self.xsession_wrapper = self.option("xsession-wrapper").or_else(|| Some(DEFAULT_XSESSION_WRAPPER.to_string()));
     }
 
+    self.hide_x11_if_unavailable = self.config().opt_present("hide-x11-if-unavailable");
+    self.xdg_env_name = self.option("xdg-env-name");
+    self.maintenance_session = self.option("maintenance-session");
+
+    if let Some(overrides) = self.options_multi("session-desktop-names") {
+      for value in overrides {
+        match value.split_once('=') {
+          Some((slug, names)) if !slug.is_empty() => self.session_desktop_names.push((slug.to_string(), names.to_string())),
+          _ => return Err(format!("malformed session desktop names definition for '{value}'").into()),
+        }
+      }
+    }
+
+    if let Some(users) = self.option("maintenance-session-users") {
+      if self.maintenance_session.is_none() {
+        return Err("--maintenance-session-users requires --maintenance-session".into());
+      }
+
+      self.maintenance_session_users = users.split(',').map(str::trim).filter(|user| !user.is_empty()).map(str::to_string).collect();
+    }
+
+    self.parse_activation_token(|name| env::var(name).ok());
+
+    if let Some(vars) = self.option("strip-env") {
+      self.strip_env = vars.split(',').map(str::trim).filter(|var| !var.is_empty()).map(str::to_string).collect();
+    }
+
     if self.config().opt_present("issue") {
-      self.greeting = get_issue();
+      self.greeting = get_issue(&MESSAGES);
+    }
+
+    if let Some(ref title) = self.power_title {
+      self.powers.title = title.clone();
     }
 
-    self.powers.options.push(Power {
-      action: PowerOption::Shutdown,
-      label: fl!("shutdown"),
-      command: self.config().opt_str("power-shutdown"),
-    });
+    let power_options = match self.option("power-options") {
+      None => vec![PowerOption::Shutdown, PowerOption::Reboot],
+      Some(options) => {
+        let mut power_options = vec![];
+
+        for option in options.split(',').map(str::trim) {
+          let option = match option {
+            "shutdown" => PowerOption::Shutdown,
+            "reboot" => PowerOption::Reboot,
+            _ => return Err("--power-options must be a comma-separated list of 'shutdown' or 'reboot'".into()),
+          };
+
+          if !power_options.contains(&option) {
+            power_options.push(option);
+          }
+        }
+
+        power_options
+      }
+    };
 
-    self.powers.options.push(Power {
-      action: PowerOption::Reboot,
-      label: fl!("reboot"),
-      command: self.config().opt_str("power-reboot"),
-    });
+    for option in power_options {
+      self.powers.options.push(match option {
+        PowerOption::Shutdown => Power {
+          action: PowerOption::Shutdown,
+          label: fl!("shutdown"),
+          command: self.config().opt_str("power-shutdown"),
+        },
+
+        PowerOption::Reboot => Power {
+          action: PowerOption::Reboot,
+          label: fl!("reboot"),
+          command: self.config().opt_str("power-reboot"),
+        },
+      });
+    }
 
     self.power_setsid = !self.config().opt_present("power-no-setsid");
+    self.show_power_commands = self.config().opt_present("show-power-commands");
+    self.power_stay_on_failure = self.config().opt_present("power-stay-on-failure");
 
     self.kb_command = self.config().opt_str("kb-command").map(|i| i.parse::<u8>().unwrap_or_default()).unwrap_or(2);
     self.kb_sessions = self.config().opt_str("kb-sessions").map(|i| i.parse::<u8>().unwrap_or_default()).unwrap_or(3);
@@ -626,9 +1201,33 @@ impl Greeter {
       return Err("keybindings must all be distinct".into());
     }
 
+    self.vi_keys = self.config().opt_present("vi-keys");
+
+    if let Some(key) = self.option("submit-key") {
+      self.submit_modifiers = match key.as_str() {
+        "enter" => KeyModifiers::NONE,
+        "alt+enter" => KeyModifiers::ALT,
+
+        // The Linux console reports these as a plain Enter, so nobody could log
+        // in from it.
+        "ctrl+enter" | "shift+enter" => return Err(format!("submit key '{key}' cannot be told apart from Enter on the console").into()),
+
+        _ => return Err(format!("unknown submit key '{key}'").into()),
+      };
+    }
+
     Ok(())
   }
 
+  // Returns the text to display for a message from greetd, replaced by the
+  // first configured override it matches.
+  pub fn override_prompt<'m>(&self, message: &'m str) -> Cow<'m, str> {
+    match self.prompt_overrides.iter().find(|(pattern, _)| message.contains(pattern.as_str())) {
+      Some((_, replacement)) => Cow::Owned(replacement.clone()),
+      None => Cow::Borrowed(message),
+    }
+  }
+
   pub fn set_prompt(&mut self, prompt: &str) {
     self.prompt = if prompt.ends_with(' ') { Some(prompt.into()) } else { Some(format!("{prompt} ")) };
   }
@@ -637,103 +1236,859 @@ impl Greeter {
     self.prompt = None;
   }
 
-  // Computes the size of the prompt to help determine where input should start.
-  pub fn prompt_width(&self) -> usize {
-    match &self.prompt {
-      None => 0,
-      Some(prompt) => prompt.chars().count(),
+  // Keeps the activation token found in the environment given by `lookup`, to
+  // hand it off to the started session, if requested.
+  pub fn parse_activation_token<F>(&mut self, lookup: F)
+  where
+    F: Fn(&str) -> Option<String>,
+  {
+    if self.config().opt_present("pass-activation-token") {
+      self.activation_token = lookup("XDG_ACTIVATION_TOKEN").filter(|token| !token.is_empty());
     }
   }
-}
 
-fn print_usage(opts: Options) {
-  eprint!("{}", opts.usage("Usage: tuigreet [OPTIONS]"));
-}
+  // Selects the default command to run, if any, along with its environment.
+  //
+  // If the `--cmd` argument is provided, it will override the selected
+  // session. Otherwise, a default command can be handed off through the
+  // `TUIGREET_CMD` and `TUIGREET_ENV` environment variables, the latter
+  // containing whitespace-separated variable definitions.
+  //
+  // With `--expand-cmd-env`, `${VAR}` references in the command are replaced
+  // with the value of the variables, looked up in the same way.
+  pub fn parse_default_command<F>(&mut self, lookup: F) -> Result<(), Box<dyn Error>>
+  where
+    F: Fn(&str) -> Option<String>,
+  {
+    let default = match self.option("cmd") {
+      Some(command) => Some((command, self.options_multi("env"))),
 
-fn print_version() {
-  println!("tuigreet {} ({})", env!("VERSION"), env!("TARGET"));
-  println!("Copyright (C) 2020 Antoine POPINEAU <https://github.com/apognu/tuigreet>.");
-  println!("Licensed under GPLv3+ (GNU GPL version 3 or later).");
-  println!();
-  println!("This is free software, you are welcome to redistribute it under some conditions.");
-  println!("There is NO WARRANTY, to the extent provided by law.");
-}
+      None => lookup("TUIGREET_CMD").filter(|command| !command.trim().is_empty()).map(|command| {
+        let envs = lookup("TUIGREET_ENV")
+          .map(|envs| envs.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+          .filter(|envs| !envs.is_empty());
 
-#[cfg(test)]
-mod test {
-  use crate::{ui::sessions::SessionSource, Greeter, SecretDisplay};
+        (command, envs)
+      }),
+    };
 
-  #[test]
-  fn test_prompt_width() {
-    let mut greeter = Greeter::default();
-    greeter.prompt = None;
+    if let Some((mut command, envs)) = default {
+      if self.config().opt_present("expand-cmd-env") {
+        command = expand_env(&command, &lookup);
+      }
 
-    assert_eq!(greeter.prompt_width(), 0);
+      if let Some(ref envs) = envs {
+        for env in envs {
+          if !env.contains('=') {
+            return Err(format!("malformed environment variable definition for '{env}'").into());
+          }
+        }
+      }
 
-    greeter.prompt = Some("Hello:".into());
+      self.session_source = SessionSource::DefaultCommand(command, envs);
+    }
 
-    assert_eq!(greeter.prompt_width(), 6);
+    Ok(())
   }
 
-  #[test]
-  fn test_set_prompt() {
-    let mut greeter = Greeter::default();
-
-    greeter.set_prompt("Hello:");
+  // Selects the free-form command or the session remembered for the user. Only
+  // one of them is normally remembered, the last one that was chosen, but if
+  // both are found, the preferred one is selected, unless it is unavailable.
+  pub fn restore_user_session_source(&mut self, command: Option<String>, session: Option<PathBuf>) {
+    let select_command = |greeter: &mut Greeter| {
+      if let Some(ref command) = command {
+        tracing::info!("remembered user command is {}", command);
 
-    assert_eq!(greeter.prompt, Some("Hello: ".into()));
+        greeter.session_source = SessionSource::Command(command.clone());
+      }
+    };
 
-    greeter.set_prompt("Hello World: ");
+    match self.remember_user_session_prefer {
+      RememberPreference::Session => {
+        select_command(self);
 
-    assert_eq!(greeter.prompt, Some("Hello World: ".into()));
+        if let Some(session) = session {
+          self.select_remembered_session(session);
+        }
+      }
 
-    greeter.remove_prompt();
+      RememberPreference::Command => {
+        if let Some(session) = session {
+          self.select_remembered_session(session);
+        }
 
-    assert_eq!(greeter.prompt, None);
+        select_command(self);
+      }
+    }
   }
 
-  #[tokio::test]
-  async fn test_command_line_arguments() {
-    let table: &[(&[&str], _, Option<fn(&Greeter)>)] = &[
-      // No arguments
-      (&[], true, None),
-      // Valid combinations
-      (&["--cmd", "hello"], true, None),
-      (
-        &[
-          "--cmd",
-          "uname",
-          "--env",
-          "A=B",
-          "--env",
-          "C=D=E",
-          "--asterisks",
-          "--asterisks-char",
-          ".",
-          "--issue",
-          "--time",
-          "--prompt-padding",
-          "0",
-          "--window-padding",
-          "1",
-          "--container-padding",
-          "12",
-          "--user-menu",
-        ],
-        true,
-        Some(|greeter| {
-          assert!(matches!(&greeter.session_source, SessionSource::DefaultCommand(cmd, Some(env)) if cmd == "uname" && env.len() == 2));
+  // Selects the session remembered for the user, if it still exists. Otherwise,
+  // the current session selection is kept as is.
+  pub fn select_remembered_session(&mut self, last_session: PathBuf) {
+    match self.sessions.options.iter().position(|session| session.path.as_deref() == Some(last_session.as_path())) {
+      Some(index) => {
+        tracing::info!("remembered user session is {}", self.sessions.options[index].name);
 
-          if let SessionSource::DefaultCommand(_, Some(env)) = &greeter.session_source {
-            assert_eq!(env[0], "A=B");
-            assert_eq!(env[1], "C=D=E");
-          }
+        self.sessions.selected = index;
+        self.session_source = SessionSource::Session(index);
+      }
+
+      None => tracing::warn!("remembered user session '{}' is not available anymore", last_session.display()),
+    }
+  }
+
+  // Preselects the session named by the `TUIGREET_SESSION` environment
+  // variable, matched against the slug or the name of sessions. A default
+  // command provided on the command line takes precedence.
+  pub fn preselect_session<F>(&mut self, lookup: F)
+  where
+    F: Fn(&str) -> Option<String>,
+  {
+    if !matches!(self.session_source, SessionSource::Session(_)) {
+      return;
+    }
+
+    if let Some(name) = lookup("TUIGREET_SESSION").filter(|name| !name.trim().is_empty()) {
+      let name = name.trim();
+
+      match self.sessions.options.iter().position(|session| session.slug.as_deref() == Some(name) || session.name == name) {
+        Some(index) => {
+          tracing::info!("preselecting session {} from the environment", name);
+
+          self.sessions.selected = index;
+          self.session_source = SessionSource::Session(index);
+        }
+
+        None => tracing::info!("session {} from the environment was not found", name),
+      }
+    }
+  }
+
+  // Sets the message displayed to the user, recording when it was shown.
+  pub fn set_message(&mut self, message: String) {
+    self.message = Some(message);
+    self.message_time = Some(Instant::now());
+    self.message_error = false;
+  }
+
+  // Sets a message reporting an error, which may stay displayed longer.
+  pub fn set_error(&mut self, message: String) {
+    self.set_message(message);
+    self.message_error = true;
+  }
+
+  // Clears the displayed message once it was displayed for longer than the
+  // configured timeout. Errors use the same timeout unless given their own.
+  pub fn expire_message(&mut self, now: Instant) {
+    let timeout = match self.message_error {
+      true => self.error_message_timeout.or(self.message_timeout),
+      false => self.message_timeout,
+    };
+
+    if let (Some(time), Some(timeout)) = (self.message_time, timeout) {
+      if now.duration_since(time) >= timeout {
+        self.message.zeroize();
+        self.message_time = None;
+      }
+    }
+  }
+
+  // Clears the displayed message, unless it was set less than the configured
+  // feedback delay ago, so the user has a chance to read it.
+  pub fn clear_message(&mut self) {
+    if let Some(time) = self.message_time {
+      if time.elapsed() < self.password_feedback_delay {
+        return;
+      }
+    }
+
+    self.message = None;
+    self.message_time = None;
+  }
+
+  // Switches to the next configured greeting, if more than one was provided.
+  pub fn rotate_greeting(&mut self) {
+    if self.greetings.len() < 2 {
+      return;
+    }
+
+    self.greeting_index = (self.greeting_index + 1) % self.greetings.len();
+    self.greeting = Some(self.greetings[self.greeting_index].clone());
+    self.greeting_time = Some(Instant::now());
+  }
+
+  // Reloads the list of sessions from disk. The selected and the chosen sessions
+  // are looked up again by path, since their position may have changed.
+  pub fn refresh_sessions(&mut self) {
+    let path = |sessions: &[Session], index: usize| sessions.get(index).and_then(|session| session.path.clone());
+
+    let selected = path(&self.sessions.options, self.sessions.selected);
+    let chosen = match self.session_source {
+      SessionSource::Session(index) => Some(path(&self.sessions.options, index)),
+      _ => None,
+    };
+
+    let sessions = get_sessions(self).unwrap_or_default();
+    let position = |path: Option<PathBuf>| path.and_then(|path| sessions.iter().position(|session| session.path.as_ref() == Some(&path)));
+
+    tracing::info!("reloaded {} sessions", sessions.len());
+
+    self.sessions.selected = position(selected).unwrap_or_default();
+
+    if let Some(chosen) = chosen {
+      self.session_source = match position(chosen) {
+        Some(index) => SessionSource::Session(index),
+
+        // The chosen session was removed, so it must be chosen again.
+        None => {
+          self.session_chosen = false;
+
+          match sessions.is_empty() {
+            true => SessionSource::None,
+            false => SessionSource::Session(0),
+          }
+        }
+      };
+    }
+
+    self.sessions.options = sessions;
+  }
+
+  // Switches to a popup screen, such as a menu or the command prompt. When
+  // switching from another popup, the screen it was opened from is kept, so that
+  // closing the new one never goes back to a popup.
+  pub fn enter_transient_mode(&mut self, mode: Mode) {
+    if !matches!(self.mode, Mode::Users | Mode::Command | Mode::Sessions | Mode::Power | Mode::Processing) {
+      self.previous_mode = self.mode;
+    }
+
+    self.mode = mode;
+  }
+
+  // Whether the current user was excluded from having their username and
+  // sessions remembered. The guest user is never remembered.
+  pub fn is_remember_excluded(&self) -> bool {
+    self.is_guest() || self.remember_exclude_users.contains(&self.username.value)
+  }
+
+  // Whether the current user is the configured guest user.
+  pub fn is_guest(&self) -> bool {
+    matches!(self.guest_user, Some(ref guest) if *guest == self.username.value)
+  }
+
+  // Whether the current user may start the provided session. The maintenance
+  // session can only be started by the users it was restricted to.
+  pub fn is_session_allowed(&self, session: &Session) -> bool {
+    match self.maintenance_session {
+      Some(ref slug) if session.slug.as_ref() == Some(slug) => self.maintenance_session_users.contains(&self.username.value),
+      _ => true,
+    }
+  }
+
+  // Whether the session tabs should be displayed, which is only useful if
+  // there is more than one session to pick from.
+  pub fn show_session_tabs(&self) -> bool {
+    self.sessions_tabs && self.sessions.options.len() > 1
+  }
+
+  // Returns the message displayed after a failed authentication attempt.
+  pub fn failure_message(&self) -> String {
+    match self.retry_message {
+      Some(ref retry) => format!("{}\n{retry}", fl!("failed")),
+      None => fl!("failed"),
+    }
+  }
+
+  pub fn working_text(&self) -> String {
+    match self.working_text {
+      Some(ref text) if !text.is_empty() => text.clone(),
+      _ => fl!("wait"),
+    }
+  }
+
+  // Computes the size of the prompt to help determine where input should start.
+  pub fn prompt_width(&self) -> usize {
+    match &self.prompt {
+      None => 0,
+      Some(prompt) => prompt.chars().count(),
+    }
+  }
+}
+
+// Replaces `${VAR}` references with the value of the variables. Undefined
+// variables are replaced with an empty string, and an unterminated reference is
+// kept as is.
+fn expand_env<F>(command: &str, lookup: &F) -> String
+where
+  F: Fn(&str) -> Option<String>,
+{
+  let mut expanded = String::with_capacity(command.len());
+  let mut rest = command;
+
+  while let Some(start) = rest.find("${") {
+    let end = match rest[start..].find('}') {
+      Some(end) => start + end,
+      None => break,
+    };
+
+    let name = &rest[start + 2..end];
+
+    expanded.push_str(&rest[..start]);
+
+    match lookup(name) {
+      Some(value) => expanded.push_str(&value),
+      None => tracing::debug!("environment variable {} is not defined, expanding to an empty string", name),
+    }
+
+    rest = &rest[end + 1..];
+  }
+
+  expanded.push_str(rest);
+  expanded
+}
+
+fn print_usage(opts: Options) {
+  let usage = opts.usage("Usage: tuigreet [OPTIONS]");
+  let mut hidden = false;
+
+  for line in usage.lines() {
+    // Descriptions of long options are wrapped on the following lines, which
+    // should be skipped along with the option.
+    if line.trim_start().starts_with('-') {
+      hidden = HIDDEN_OPTIONS.iter().any(|option| line.contains(option));
+    }
+
+    if !hidden {
+      eprintln!("{line}");
+    }
+  }
+}
+
+fn print_version() {
+  println!("tuigreet {} ({})", env!("VERSION"), env!("TARGET"));
+  println!("Copyright (C) 2020 Antoine POPINEAU <https://github.com/apognu/tuigreet>.");
+  println!("Licensed under GPLv3+ (GNU GPL version 3 or later).");
+  println!();
+  println!("This is free software, you are welcome to redistribute it under some conditions.");
+  println!("There is NO WARRANTY, to the extent provided by law.");
+}
+
+fn print_locales() {
+  for locale in bundled_locales() {
+    println!("{locale}");
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+  };
+
+  use crossterm::event::KeyModifiers;
+
+  use crate::{
+    power::PowerOption,
+    ui::{
+      common::{masked::MaskedString, menu::Menu},
+      sessions::{Session, SessionSource},
+    },
+    Greeter, GreetingRotation, Mode, PromptOrder, RememberPreference, SecretDisplay,
+  };
+
+  #[test]
+  fn test_enter_transient_mode() {
+    let mut greeter = Greeter::default();
+    greeter.mode = Mode::Password;
+
+    greeter.enter_transient_mode(Mode::Sessions);
+
+    assert_eq!(greeter.mode, Mode::Sessions);
+    assert_eq!(greeter.previous_mode, Mode::Password);
+
+    for mode in [Mode::Power, Mode::Command, Mode::Users, Mode::Sessions].iter() {
+      greeter.enter_transient_mode(*mode);
+
+      assert_eq!(greeter.mode, *mode);
+      assert_eq!(greeter.previous_mode, Mode::Password);
+    }
+
+    greeter.mode = Mode::Processing;
+    greeter.enter_transient_mode(Mode::Power);
+
+    assert_eq!(greeter.previous_mode, Mode::Password);
+
+    greeter.mode = Mode::Action;
+    greeter.enter_transient_mode(Mode::Power);
+
+    assert_eq!(greeter.mode, Mode::Power);
+    assert_eq!(greeter.previous_mode, Mode::Action);
+  }
+
+  #[test]
+  fn test_prompt_width() {
+    let mut greeter = Greeter::default();
+    greeter.prompt = None;
+
+    assert_eq!(greeter.prompt_width(), 0);
+
+    greeter.prompt = Some("Hello:".into());
+
+    assert_eq!(greeter.prompt_width(), 6);
+  }
+
+  #[test]
+  fn test_set_prompt() {
+    let mut greeter = Greeter::default();
+
+    greeter.set_prompt("Hello:");
+
+    assert_eq!(greeter.prompt, Some("Hello: ".into()));
+
+    greeter.set_prompt("Hello World: ");
+
+    assert_eq!(greeter.prompt, Some("Hello World: ".into()));
+
+    greeter.remove_prompt();
+
+    assert_eq!(greeter.prompt, None);
+  }
+
+  #[test]
+  fn test_activation_token_from_environment() {
+    let lookup = |name: &str| match name {
+      "XDG_ACTIVATION_TOKEN" => Some("mytoken".to_string()),
+      _ => None,
+    };
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(["--pass-activation-token"]).ok();
+    greeter.parse_activation_token(lookup);
+    assert_eq!(greeter.activation_token.as_deref(), Some("mytoken"));
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(["--pass-activation-token"]).ok();
+    greeter.parse_activation_token(|_| Some(String::new()));
+    assert_eq!(greeter.activation_token, None);
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(Vec::<String>::new()).ok();
+    greeter.parse_activation_token(lookup);
+    assert_eq!(greeter.activation_token, None);
+  }
+
+  #[test]
+  fn test_default_command_from_environment() {
+    let lookup = |name: &str| match name {
+      "TUIGREET_CMD" => Some("sway".to_string()),
+      "TUIGREET_ENV" => Some("A=B C=D".to_string()),
+      _ => None,
+    };
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&[""]).ok();
+
+    assert!(greeter.parse_default_command(lookup).is_ok());
+    assert!(matches!(&greeter.session_source, SessionSource::DefaultCommand(cmd, Some(env)) if cmd == "sway" && env == &["A=B", "C=D"]));
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--cmd", "uname"]).ok();
+
+    assert!(greeter.parse_default_command(lookup).is_ok());
+    assert!(matches!(&greeter.session_source, SessionSource::DefaultCommand(cmd, None) if cmd == "uname"));
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&[""]).ok();
+
+    assert!(greeter.parse_default_command(|_| None).is_ok());
+    assert!(matches!(&greeter.session_source, SessionSource::None));
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&[""]).ok();
+
+    let malformed = |name: &str| match name {
+      "TUIGREET_CMD" => Some("sway".to_string()),
+      "TUIGREET_ENV" => Some("A".to_string()),
+      _ => None,
+    };
+
+    assert!(greeter.parse_default_command(malformed).is_err());
+  }
+
+  #[test]
+  fn test_remembered_session() {
+    let mut greeter = Greeter::default();
+    greeter.session_source = SessionSource::Session(1);
+    greeter.sessions = Menu::<Session> {
+      title: "Sessions".into(),
+      selected: 1,
+      options: vec![
+        Session {
+          name: "Session1".into(),
+          path: Some("/Session1Path".into()),
+          ..Default::default()
+        },
+        Session {
+          name: "Session2".into(),
+          path: Some("/Session2Path".into()),
+          ..Default::default()
+        },
+      ],
+      expand: None,
+    };
+
+    greeter.select_remembered_session(PathBuf::from("/StalePath"));
+
+    assert_eq!(greeter.sessions.selected, 1);
+    assert!(matches!(greeter.session_source, SessionSource::Session(1)));
+
+    greeter.select_remembered_session(PathBuf::from("/Session1Path"));
+
+    assert_eq!(greeter.sessions.selected, 0);
+    assert!(matches!(greeter.session_source, SessionSource::Session(0)));
+  }
+
+  #[test]
+  fn test_remembered_user_session_preference() {
+    let sessions = || Menu::<Session> {
+      title: "Sessions".into(),
+      selected: 0,
+      options: vec![Session {
+        name: "Session1".into(),
+        path: Some("/Session1Path".into()),
+        ..Default::default()
+      }],
+      expand: None,
+    };
+
+    for (prefer, command, session, expected) in [
+      (RememberPreference::Session, true, true, "session"),
+      (RememberPreference::Command, true, true, "command"),
+      (RememberPreference::Session, true, false, "command"),
+      (RememberPreference::Command, false, true, "session"),
+      (RememberPreference::Session, true, true, "stale"),
+    ]
+    .iter()
+    {
+      let mut greeter = Greeter::default();
+      greeter.sessions = sessions();
+      greeter.remember_user_session_prefer = *prefer;
+
+      let command = if *command { Some("uname".to_string()) } else { None };
+      let session = match (*session, *expected) {
+        (true, "stale") => Some(PathBuf::from("/StalePath")),
+        (true, _) => Some(PathBuf::from("/Session1Path")),
+        (false, _) => None,
+      };
+
+      greeter.restore_user_session_source(command, session);
+
+      match *expected {
+        "session" => assert!(matches!(greeter.session_source, SessionSource::Session(0))),
+        _ => assert!(matches!(&greeter.session_source, SessionSource::Command(command) if command == "uname")),
+      }
+    }
+  }
+
+  #[test]
+  fn test_preselect_session_from_environment() {
+    let sessions = || Menu::<Session> {
+      title: "Sessions".into(),
+      selected: 0,
+      options: vec![
+        Session {
+          slug: Some("gnome".into()),
+          name: "GNOME".into(),
+          ..Default::default()
+        },
+        Session {
+          slug: Some("sway".into()),
+          name: "Sway".into(),
+          ..Default::default()
+        },
+      ],
+      expand: None,
+    };
+
+    let lookup = |value: &'static str| move |name: &str| if name == "TUIGREET_SESSION" { Some(value.to_string()) } else { None };
+
+    let mut greeter = Greeter::default();
+    greeter.sessions = sessions();
+    greeter.session_source = SessionSource::Session(0);
+    greeter.preselect_session(lookup("sway"));
+
+    assert_eq!(greeter.sessions.selected, 1);
+    assert!(matches!(greeter.session_source, SessionSource::Session(1)));
+
+    greeter.preselect_session(lookup("GNOME"));
+
+    assert_eq!(greeter.sessions.selected, 0);
+    assert!(matches!(greeter.session_source, SessionSource::Session(0)));
+
+    greeter.preselect_session(lookup("unknown"));
+
+    assert!(matches!(greeter.session_source, SessionSource::Session(0)));
+
+    let mut greeter = Greeter::default();
+    greeter.sessions = sessions();
+    greeter.session_source = SessionSource::DefaultCommand("uname".into(), None);
+    greeter.preselect_session(lookup("sway"));
+
+    assert_eq!(greeter.sessions.selected, 0);
+    assert!(matches!(greeter.session_source, SessionSource::DefaultCommand(..)));
+  }
+
+  #[test]
+  fn test_expand_default_command_env() {
+    let lookup = |name: &str| match name {
+      "TUIGREET_CMD" => Some("start ${DISPLAY}".to_string()),
+      "DISPLAY" => Some(":0".to_string()),
+      _ => None,
+    };
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&[""]).ok();
+
+    assert!(greeter.parse_default_command(lookup).is_ok());
+    assert!(matches!(&greeter.session_source, SessionSource::DefaultCommand(cmd, None) if cmd == "start ${DISPLAY}"));
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--expand-cmd-env"]).ok();
+
+    assert!(greeter.parse_default_command(lookup).is_ok());
+    assert!(matches!(&greeter.session_source, SessionSource::DefaultCommand(cmd, None) if cmd == "start :0"));
+
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--expand-cmd-env", "--cmd", "sway --config ${UNDEFINED}/sway ${DISPLAY} ${"]).ok();
+
+    assert!(greeter.parse_default_command(lookup).is_ok());
+    assert!(matches!(&greeter.session_source, SessionSource::DefaultCommand(cmd, None) if cmd == "sway --config /sway :0 ${"));
+  }
+
+  #[test]
+  fn test_keep_password_on_soft_scrub() {
+    let mut greeter = Greeter::default();
+    greeter.keep_password_on_failure = true;
+    greeter.previous_secret = Some("hunter2".into());
+    greeter.cursor_offset = -3;
+
+    greeter.scrub(false, true);
+
+    assert_eq!(greeter.buffer, "hunter2");
+    assert_eq!(greeter.cursor_offset, 0);
+    assert!(greeter.previous_secret.is_none());
+
+    greeter.previous_secret = Some("hunter2".into());
+    greeter.scrub(false, false);
+
+    assert_eq!(greeter.buffer, "");
+    assert!(greeter.previous_secret.is_none());
+  }
+
+  #[test]
+  fn test_drop_password_on_soft_scrub() {
+    let mut greeter = Greeter::default();
+    greeter.previous_secret = Some("hunter2".into());
+    greeter.buffer = "hunter2".into();
+
+    greeter.scrub(false, true);
+
+    assert_eq!(greeter.buffer, "");
+    assert!(greeter.previous_secret.is_none());
+  }
+
+  #[cfg(debug_assertions)]
+  #[test]
+  fn test_no_scrub() {
+    let mut greeter = Greeter::default();
+    greeter.no_scrub = true;
+    greeter.buffer = "hunter2".into();
+    greeter.username = MaskedString::from("apognu".into(), None);
+
+    greeter.teardown();
+
+    assert_eq!(greeter.buffer, "hunter2");
+    assert_eq!(greeter.username.value, "apognu");
+
+    // Resets still behave as usual, only the final scrub is skipped.
+    greeter.scrub(false, true);
+
+    assert_eq!(greeter.buffer, "");
+    assert_eq!(greeter.username.value, "apognu");
+
+    greeter.buffer = "hunter2".into();
+    greeter.no_scrub = false;
+    greeter.teardown();
+
+    assert_eq!(greeter.buffer, "");
+    assert_eq!(greeter.username.value, "");
+  }
+
+  #[test]
+  fn test_remember_exclude_users() {
+    let mut greeter = Greeter::default();
+    greeter.remember_exclude_users = vec!["root".to_string(), "admin".to_string()];
+
+    greeter.username = MaskedString::from("admin".into(), None);
+    assert!(greeter.is_remember_excluded());
+
+    greeter.username = MaskedString::from("apognu".into(), None);
+    assert!(!greeter.is_remember_excluded());
+  }
+
+  #[test]
+  fn test_override_prompt() {
+    let mut greeter = Greeter::default();
+
+    assert_eq!(greeter.override_prompt("Password: "), "Password: ");
+
+    greeter.prompt_overrides = vec![
+      ("Password".to_string(), "Enter your company password:".to_string()),
+      ("Verification code".to_string(), "Enter the code from your phone:".to_string()),
+    ];
+
+    assert_eq!(greeter.override_prompt("Password: "), "Enter your company password:");
+    assert_eq!(greeter.override_prompt("Verification code: "), "Enter the code from your phone:");
+    assert_eq!(greeter.override_prompt("Token: "), "Token: ");
+  }
+
+  #[test]
+  fn test_rotate_greeting() {
+    let mut greeter = Greeter::default();
+    greeter.greetings = vec!["Hello".into(), "Bonjour".into()];
+    greeter.greeting = Some("Hello".into());
+
+    greeter.rotate_greeting();
+
+    assert_eq!(greeter.greeting_index, 1);
+    assert_eq!(greeter.greeting.as_deref(), Some("Bonjour"));
+
+    greeter.rotate_greeting();
+
+    assert_eq!(greeter.greeting_index, 0);
+    assert_eq!(greeter.greeting.as_deref(), Some("Hello"));
+  }
+
+  #[test]
+  fn test_rotate_single_greeting() {
+    let mut greeter = Greeter::default();
+    greeter.greetings = vec!["Hello".into()];
+    greeter.greeting = Some("Hello".into());
+
+    greeter.rotate_greeting();
+
+    assert_eq!(greeter.greeting_index, 0);
+    assert_eq!(greeter.greeting.as_deref(), Some("Hello"));
+  }
+
+  #[test]
+  fn test_expire_message() {
+    let mut greeter = Greeter::default();
+    greeter.message_timeout = Some(Duration::from_secs(5));
+    greeter.error_message_timeout = Some(Duration::from_secs(30));
+
+    greeter.set_message("Last login".into());
+    greeter.expire_message(Instant::now());
+
+    assert_eq!(greeter.message.as_deref(), Some("Last login"));
+
+    greeter.expire_message(Instant::now() + Duration::from_secs(5));
+
+    assert_eq!(greeter.message, None);
+    assert_eq!(greeter.message_time, None);
+
+    greeter.set_error("Failed".into());
+    greeter.expire_message(Instant::now() + Duration::from_secs(10));
+
+    assert_eq!(greeter.message.as_deref(), Some("Failed"));
+
+    greeter.expire_message(Instant::now() + Duration::from_secs(30));
+
+    assert_eq!(greeter.message, None);
+
+    greeter.message_timeout = None;
+    greeter.set_message("Last login".into());
+    greeter.expire_message(Instant::now() + Duration::from_secs(60));
+
+    assert_eq!(greeter.message.as_deref(), Some("Last login"));
+  }
+
+  #[test]
+  fn test_clear_message_immediately() {
+    let mut greeter = Greeter::default();
+
+    greeter.set_message("Failed".into());
+    greeter.clear_message();
+
+    assert_eq!(greeter.message, None);
+  }
+
+  #[test]
+  fn test_clear_message_after_delay() {
+    let mut greeter = Greeter::default();
+    greeter.password_feedback_delay = Duration::from_secs(1);
+
+    greeter.set_message("Failed".into());
+    greeter.clear_message();
+
+    assert_eq!(greeter.message, Some("Failed".into()));
+
+    greeter.message_time = Some(Instant::now() - Duration::from_secs(2));
+    greeter.clear_message();
+
+    assert_eq!(greeter.message, None);
+    assert_eq!(greeter.message_time, None);
+  }
+
+  #[tokio::test]
+  #[allow(clippy::type_complexity)]
+  async fn test_command_line_arguments() {
+    let table: &[(&[&str], _, Option<fn(&Greeter)>)] = &[
+      // No arguments
+      (&[], true, None),
+      // Valid combinations
+      (&["--cmd", "hello"], true, None),
+      (
+        &[
+          "--cmd",
+          "uname",
+          "--env",
+          "A=B",
+          "--env",
+          "C=D=E",
+          "--asterisks",
+          "--asterisks-char",
+          ".",
+          "--issue",
+          "--time",
+          "--prompt-padding",
+          "0",
+          "--window-padding",
+          "1",
+          "--container-padding",
+          "12",
+          "--user-menu",
+          "--password-feedback-delay",
+          "1500",
+          "--no-capslock",
+        ],
+        true,
+        Some(|greeter| {
+          assert!(matches!(&greeter.session_source, SessionSource::DefaultCommand(cmd, Some(env)) if cmd == "uname" && env.len() == 2));
+
+          if let SessionSource::DefaultCommand(_, Some(env)) = &greeter.session_source {
+            assert_eq!(env[0], "A=B");
+            assert_eq!(env[1], "C=D=E");
+          }
 
           assert!(matches!(&greeter.secret_display, SecretDisplay::Character(c) if c == "."));
           assert_eq!(greeter.prompt_padding(), 0);
           assert_eq!(greeter.window_padding(), 1);
           assert_eq!(greeter.container_padding(), 13);
-          assert_eq!(greeter.user_menu, true);
+          assert!(greeter.user_menu);
+          assert_eq!(greeter.password_feedback_delay, Duration::from_millis(1500));
+          assert!(!greeter.capslock);
           assert!(matches!(greeter.xsession_wrapper.as_deref(), Some("startx /usr/bin/env")));
         }),
       ),
@@ -748,17 +2103,251 @@ mod test {
         &["--no-xsession-wrapper"],
         true,
         Some(|greeter| {
-          assert!(matches!(greeter.xsession_wrapper, None));
+          assert!(greeter.xsession_wrapper.is_none());
+        }),
+      ),
+      (
+        &["--hide-x11-if-unavailable"],
+        true,
+        Some(|greeter| {
+          assert!(greeter.hide_x11_if_unavailable);
+        }),
+      ),
+      (
+        &["--asterisks-char", "abc", "--password-mask-char-random-seed", "42"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.secret_mask_seed, 42);
+        }),
+      ),
+      (
+        &["--sessions-title", "Desktops", "--user-menu", "--users-title", "Who are you?", "--power-title", "Goodbye?"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.sessions_title.as_deref(), Some("Desktops"));
+          assert_eq!(greeter.users.title, "Who are you?");
+          assert_eq!(greeter.powers.title, "Goodbye?");
+        }),
+      ),
+      (
+        &[],
+        true,
+        Some(|greeter| {
+          assert!(greeter.powers.options.iter().map(|power| power.action).eq([PowerOption::Shutdown, PowerOption::Reboot]));
+        }),
+      ),
+      (
+        &["--power-options", "reboot"],
+        true,
+        Some(|greeter| {
+          assert!(greeter.powers.options.iter().map(|power| power.action).eq([PowerOption::Reboot]));
+        }),
+      ),
+      (
+        &["--power-options", "reboot, shutdown", "--power-reboot", "systemctl reboot"],
+        true,
+        Some(|greeter| {
+          assert!(greeter.powers.options.iter().map(|power| power.action).eq([PowerOption::Reboot, PowerOption::Shutdown]));
+          assert_eq!(greeter.powers.options[0].command.as_deref(), Some("systemctl reboot"));
+        }),
+      ),
+      (&["--power-options", "suspend"], false, None),
+      (
+        &["--maintenance-session", "rescue", "--maintenance-session-users", "root, admin"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.maintenance_session.as_deref(), Some("rescue"));
+          assert_eq!(greeter.maintenance_session_users, vec!["root", "admin"]);
+        }),
+      ),
+      (&["--maintenance-session-users", "root"], false, None),
+      (
+        &["--strip-env", "SECRET, TOKEN,"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.strip_env, vec!["SECRET", "TOKEN"]);
+        }),
+      ),
+      (&["--power-options", ""], false, None),
+      (
+        &["--user-menu", "--user-menu-min-uid", "60000", "--user-menu-max-uid", "60001"],
+        true,
+        Some(|greeter| {
+          assert!(greeter.users.options.is_empty());
+          assert!(!greeter.user_menu);
+        }),
+      ),
+      (
+        &["--user-menu", "--user-menu-min-uid", "60000", "--user-menu-max-uid", "60001", "--guest-user", "guest"],
+        true,
+        Some(|greeter| {
+          assert!(greeter.user_menu);
+          assert_eq!(greeter.users.options.len(), 1);
+          assert_eq!(greeter.users.options[0].username, "guest");
+          assert_eq!(greeter.guest_command, None);
+        }),
+      ),
+      (&["--guest-command", "kiosk"], false, None),
+      (
+        &["--user-menu-format", "%u - %n"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.user_menu_format.as_deref(), Some("%u - %n"));
+        }),
+      ),
+      (
+        &["--user-menu", "--user-menu-min-uid", "60000", "--user-menu-max-uid", "60001", "--user-menu-require-users"],
+        false,
+        None,
+      ),
+      (
+        &["--remember"],
+        true,
+        Some(|greeter| {
+          assert!(greeter.auto_create_session);
+        }),
+      ),
+      (
+        &["--remember", "--no-auto-create-session"],
+        true,
+        Some(|greeter| {
+          assert!(!greeter.auto_create_session);
+        }),
+      ),
+      (
+        &["--remember", "--remember-exclude-user", "root, admin,"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.remember_exclude_users, vec!["root", "admin"]);
+        }),
+      ),
+      (
+        &["--prompt-override", "Password=Company password:", "--prompt-override", "OTP=Code: a=b"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.prompt_overrides[0], ("Password".to_string(), "Company password:".to_string()));
+          assert_eq!(greeter.prompt_overrides[1], ("OTP".to_string(), "Code: a=b".to_string()));
+        }),
+      ),
+      (&["--prompt-override", "Password"], false, None),
+      (
+        &["--session-desktop-names", "sway=sway:wlroots", "--session-desktop-names", "gnome=GNOME"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.session_desktop_names[0], ("sway".to_string(), "sway:wlroots".to_string()));
+          assert_eq!(greeter.session_desktop_names[1], ("gnome".to_string(), "GNOME".to_string()));
+        }),
+      ),
+      (&["--session-desktop-names", "=GNOME"], false, None),
+      (
+        &["--submit-key", "alt+enter"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.submit_modifiers, KeyModifiers::ALT);
+        }),
+      ),
+      (&["--submit-key", "ctrl+enter"], false, None),
+      (&["--submit-key", "shift+enter"], false, None),
+      (&["--submit-key", "super+enter"], false, None),
+      (&["--prompt-override", "=Password"], false, None),
+      (
+        &["--retry-message", "Check caps lock and try again."],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.failure_message(), format!("{}\nCheck caps lock and try again.", fl!("failed")));
+        }),
+      ),
+      (
+        &["--retry-message", ""],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.failure_message(), fl!("failed"));
+        }),
+      ),
+      (
+        &["--working-text", "Hang on..."],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.working_text(), "Hang on...");
+        }),
+      ),
+      (
+        &["--locale-time-format"],
+        true,
+        Some(|greeter| {
+          assert!(greeter.locale_time_format);
+          assert!(greeter.time_format.is_none());
+        }),
+      ),
+      (
+        &["--greetd-socket", "tcp://127.0.0.1:4242"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.socket, "tcp://127.0.0.1:4242");
+        }),
+      ),
+      (
+        &["--secret-progress"],
+        true,
+        Some(|greeter| {
+          assert!(matches!(greeter.secret_display, SecretDisplay::Progress));
+        }),
+      ),
+      (
+        &["--prompt-order", "password-first"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.prompt_order, PromptOrder::PasswordFirst);
+        }),
+      ),
+      (
+        &["--greeting", "Hello", "--greeting", "Bonjour", "--greeting-rotate", "keypress"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.greetings, vec!["Hello".to_string(), "Bonjour".to_string()]);
+          assert_eq!(greeter.greeting.as_deref(), Some("Hello"));
+          assert_eq!(greeter.greeting_rotate, GreetingRotation::Keypress);
+        }),
+      ),
+      (
+        &["--show-diskfree", "/home"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.diskfree_mountpoint.as_deref(), Some(std::path::Path::new("/home")));
+        }),
+      ),
+      (
+        &["--remember", "--remember-user-session", "--remember-user-session-prefer", "command"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.remember_user_session_prefer, RememberPreference::Command);
+        }),
+      ),
+      (
+        &["--message-timeout", "5", "--error-message-timeout", "30"],
+        true,
+        Some(|greeter| {
+          assert_eq!(greeter.message_timeout, Some(Duration::from_secs(5)));
+          assert_eq!(greeter.error_message_timeout, Some(Duration::from_secs(30)));
         }),
       ),
       // Invalid combinations
       (&["--remember-session", "--remember-user-session"], false, None),
       (&["--asterisk-char", ""], false, None),
       (&["--remember-user-session"], false, None),
+      (&["--remember", "--remember-user-session", "--remember-user-session-prefer", "both"], false, None),
       (&["--min-uid", "10000", "--max-uid", "5000"], false, None),
       (&["--issue", "--greeting", "Hello, world!"], false, None),
+      (&["--greeting-rotate", "hourly"], false, None),
+      (&["--prompt-order", "random"], false, None),
+      (&["--asterisks", "--secret-progress"], false, None),
+      (&["--password-mask-char-random-seed", "-1"], false, None),
       (&["--kb-command", "F2", "--kb-sessions", "F2"], false, None),
       (&["--time-format", "%i %"], false, None),
+      (&["--password-feedback-delay", "soon"], false, None),
+      (&["--message-timeout", "0"], false, None),
+      (&["--error-message-timeout", "soon"], false, None),
+      (&["--max-sessions-displayed", "0"], false, None),
       (&["--cmd", "cmd", "--env"], false, None),
       (&["--cmd", "cmd", "--env", "A"], false, None),
     ];
@@ -768,13 +2357,13 @@ mod test {
 
       match valid {
         true => {
-          assert!(matches!(greeter.parse_options(*opts).await, Ok(())), "{:?} cannot be parsed", opts);
+          assert!(greeter.parse_options(opts).await.is_ok(), "{:?} cannot be parsed", opts);
 
           if let Some(check) = check {
             check(&greeter);
           }
         }
-        false => assert!(matches!(greeter.parse_options(*opts).await, Err(_))),
+        false => assert!(greeter.parse_options(opts).await.is_err()),
       }
     }
   }
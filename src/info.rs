@@ -1,16 +1,22 @@
 use std::{
+  collections::HashMap,
   env,
   error::Error,
-  fs::{self, File},
-  io::{self, BufRead, BufReader},
+  ffi::OsStr,
+  fs::{self, File, OpenOptions},
+  io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+  os::unix::fs::PermissionsExt,
   path::{Path, PathBuf},
   process::Command,
+  sync::Mutex,
+  time::{Duration, Instant},
 };
 
-use chrono::Local;
+use chrono::{DateTime, Local, TimeZone};
+use i18n_embed::fluent::FluentLanguageLoader;
 use ini::Ini;
 use lazy_static::lazy_static;
-use nix::sys::utsname;
+use nix::sys::{statvfs, utsname};
 use utmp_rs::{UtmpEntry, UtmpParser};
 use uzers::os::unix::UserExt;
 
@@ -28,9 +34,22 @@ const LAST_USER_NAME: &str = "/var/cache/tuigreet/lastuser-name";
 const LAST_COMMAND: &str = "/var/cache/tuigreet/lastsession";
 const LAST_SESSION: &str = "/var/cache/tuigreet/lastsession-path";
 
+const LASTLOG: &str = "/var/log/lastlog";
+// Size of a `struct lastlog` record: a 32-bit timestamp followed by the line
+// and host names of the login.
+const LASTLOG_RECORD_SIZE: u64 = 4 + 32 + 256;
+
 const DEFAULT_MIN_UID: u16 = 1000;
 const DEFAULT_MAX_UID: u16 = 60000;
 
+const CAPSLOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const DISKFREE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+pub const NETWORK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+const ROUTE_FLAG_UP: u16 = 0x1;
+
+const SESSIONS_MAX_DEPTH: usize = 4;
+
 lazy_static! {
   static ref XDG_DATA_DIRS: Vec<PathBuf> = {
     let value = env::var("XDG_DATA_DIRS").unwrap_or("/usr/local/share:/usr/share".to_string());
@@ -41,6 +60,53 @@ lazy_static! {
     .map(|p| (p.join("wayland-sessions"), SessionType::Wayland))
     .chain(XDG_DATA_DIRS.iter().map(|p| (p.join("xsessions"), SessionType::X11)))
     .collect();
+  static ref XORG_AVAILABLE: bool = is_in_path(env::var_os("PATH").unwrap_or_default(), "Xorg");
+  static ref KBDINFO_AVAILABLE: bool = is_in_path(env::var_os("PATH").unwrap_or_default(), "kbdinfo");
+  static ref CAPSLOCK_STATUS: Mutex<Throttle<bool>> = Mutex::new(Throttle::new(CAPSLOCK_POLL_INTERVAL));
+}
+
+// Caches the result of an expensive probe, so it is only run once per
+// interval, however often its value is requested.
+pub struct Throttle<T> {
+  interval: Duration,
+  last: Option<(Instant, T)>,
+}
+
+impl<T> Throttle<T>
+where
+  T: Copy,
+{
+  pub fn new(interval: Duration) -> Throttle<T> {
+    Throttle { interval, last: None }
+  }
+
+  pub fn get<F>(&mut self, now: Instant, probe: F) -> T
+  where
+    F: FnOnce() -> T,
+  {
+    match self.last {
+      Some((time, value)) if now.duration_since(time) < self.interval => value,
+
+      _ => {
+        let value = probe();
+        self.last = Some((now, value));
+
+        value
+      }
+    }
+  }
+}
+
+// Checks whether an executable with the provided name exists in one of the
+// directories of a `PATH`-like value.
+pub fn is_in_path<P>(path: P, binary: &str) -> bool
+where
+  P: AsRef<OsStr>,
+{
+  env::split_paths(&path).any(|dir| match fs::metadata(dir.join(binary)) {
+    Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+    Err(_) => false,
+  })
 }
 
 pub fn get_hostname() -> String {
@@ -50,14 +116,17 @@ pub fn get_hostname() -> String {
   }
 }
 
-pub fn get_issue() -> Option<String> {
+// Reads /etc/issue, replacing the escape sequences supported by agetty. The
+// user count is formatted with the provided messages, so it follows the
+// language of the interface.
+pub fn get_issue(messages: &FluentLanguageLoader) -> Option<String> {
   let (date, time) = {
     let now = Local::now();
 
     (now.format("%a %b %_d %Y").to_string(), now.format("%H:%M:%S").to_string())
   };
 
-  let user_count = match UtmpParser::from_path("/var/run/utmp")
+  let user_count = UtmpParser::from_path("/var/run/utmp")
     .map(|utmp| {
       utmp.into_iter().fold(0, |acc, entry| match entry {
         Ok(UtmpEntry::UserProcess { .. }) => acc + 1,
@@ -65,11 +134,9 @@ pub fn get_issue() -> Option<String> {
         _ => acc,
       })
     })
-    .unwrap_or(0)
-  {
-    n if n < 2 => format!("{n} user"),
-    n => format!("{n} users"),
-  };
+    .unwrap_or(0);
+
+  let user_count = format_user_count(messages, user_count);
 
   let vtnr: usize = env::var("XDG_VTNR").unwrap_or_else(|_| "0".to_string()).parse().unwrap_or(0);
   let uts = utsname::uname();
@@ -100,44 +167,63 @@ pub fn get_issue() -> Option<String> {
   None
 }
 
-pub fn get_last_user_username() -> Option<String> {
-  match fs::read_to_string(LAST_USER_USERNAME).ok() {
+fn format_user_count(messages: &FluentLanguageLoader, count: usize) -> String {
+  i18n_embed_fl::fl!(messages, "issue_user_count", count = count).replace(['\u{2068}', '\u{2069}'], "")
+}
+
+// Reads a single value from a cache file.
+//
+// Surrounding whitespace is not significant in cache files, so it is trimmed
+// both when reading and writing values. An empty value is treated as missing.
+fn read_cache_value<P>(path: P) -> Option<String>
+where
+  P: AsRef<Path>,
+{
+  match fs::read_to_string(path).ok() {
     None => None,
-    Some(username) => {
-      let username = username.trim();
+    Some(value) => {
+      let value = value.trim();
 
-      if username.is_empty() {
+      if value.is_empty() {
         None
       } else {
-        Some(username.to_string())
+        Some(value.to_string())
       }
     }
   }
 }
 
-pub fn get_last_user_name() -> Option<String> {
-  match fs::read_to_string(LAST_USER_NAME).ok() {
-    None => None,
-    Some(name) => {
-      let name = name.trim();
+fn write_cache_value<P>(path: P, value: &str)
+where
+  P: AsRef<Path>,
+{
+  let _ = fs::write(path, value.trim());
+}
 
-      if name.is_empty() {
-        None
-      } else {
-        Some(name.to_string())
-      }
+fn write_last_username_to<P>(username_path: P, name_path: P, username: &MaskedString)
+where
+  P: AsRef<Path>,
+{
+  write_cache_value(username_path, &username.value);
+
+  match username.mask {
+    Some(ref name) if !name.trim().is_empty() => write_cache_value(name_path, name),
+    _ => {
+      let _ = fs::remove_file(name_path);
     }
   }
 }
 
-pub fn write_last_username(username: &MaskedString) {
-  let _ = fs::write(LAST_USER_USERNAME, &username.value);
+pub fn get_last_user_username() -> Option<String> {
+  read_cache_value(LAST_USER_USERNAME)
+}
 
-  if let Some(ref name) = username.mask {
-    let _ = fs::write(LAST_USER_NAME, name);
-  } else {
-    let _ = fs::remove_file(LAST_USER_NAME);
-  }
+pub fn get_last_user_name() -> Option<String> {
+  read_cache_value(LAST_USER_NAME)
+}
+
+pub fn write_last_username(username: &MaskedString) {
+  write_last_username_to(LAST_USER_USERNAME, LAST_USER_NAME, username);
 }
 
 pub fn get_last_session_path() -> Result<PathBuf, io::Error> {
@@ -194,13 +280,59 @@ pub fn delete_last_user_command(username: &str) {
   let _ = fs::remove_file(format!("{LAST_COMMAND}-{username}"));
 }
 
+// Appends the outcome of an authentication attempt to the metrics file, as a
+// line with the time, the outcome and the duration of the attempt in
+// milliseconds. Like cache files, failing to write metrics is not an error.
+pub fn write_metrics<P>(path: P, outcome: &str, duration: Duration)
+where
+  P: AsRef<Path>,
+{
+  let line = format!("{} {outcome} {}\n", Local::now().to_rfc3339(), duration.as_millis());
+
+  if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+    let _ = file.write_all(line.as_bytes());
+  }
+}
+
+// Appends a session that was started to the audit file, as a line with the
+// time, the username, the session type and the command. Line breaks in the
+// command are replaced so an entry cannot be forged.
+pub fn write_audit<P>(path: P, username: &str, session_type: SessionType, command: &str)
+where
+  P: AsRef<Path>,
+{
+  let line = format!(
+    "{} {username} {} {}\n",
+    Local::now().to_rfc3339(),
+    session_type.as_xdg_session_type(),
+    command.replace(['\r', '\n'], " ")
+  );
+
+  if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+    let _ = file.write_all(line.as_bytes());
+  }
+}
+
 pub fn get_users(min_uid: u16, max_uid: u16) -> Vec<User> {
   let users = unsafe { uzers::all_users() };
 
   let users: Vec<User> = users
     .filter(|user| user.uid() >= min_uid as u32 && user.uid() <= max_uid as u32)
-    .map(|user| User {
-      username: user.name().to_string_lossy().to_string(),
+    .filter_map(|user| {
+      // A lossy username would not match the actual account, so users whose
+      // name is not valid UTF-8 cannot be authenticated through the menu.
+      match user.name().to_str() {
+        Some(username) => Some((username.to_string(), user)),
+
+        None => {
+          tracing::warn!("skipping user {} with a non-UTF-8 username", user.uid());
+
+          None
+        }
+      }
+    })
+    .map(|(username, user)| User {
+      username,
       name: match user.gecos() {
         name if name.is_empty() => None,
         name => {
@@ -218,6 +350,23 @@ pub fn get_users(min_uid: u16, max_uid: u16) -> Vec<User> {
   users
 }
 
+// Keeps the `count` users who logged in most recently, most recent first. The
+// last remembered user, if any, comes before all others. Users who never logged
+// in are left out.
+pub fn get_recent_users<F>(users: &[User], count: usize, last_user: Option<&str>, last_login: F) -> Vec<User>
+where
+  F: Fn(&str) -> Option<DateTime<Local>>,
+{
+  let mut recent: Vec<(bool, Option<DateTime<Local>>, &User)> = users
+    .iter()
+    .map(|user| (Some(user.username.as_str()) == last_user, last_login(&user.username), user))
+    .filter(|(remembered, login, _)| *remembered || login.is_some())
+    .collect();
+
+  recent.sort_by_key(|(remembered, login, _)| std::cmp::Reverse((*remembered, *login)));
+  recent.into_iter().take(count).map(|(_, _, user)| user.clone()).collect()
+}
+
 pub fn get_min_max_uids(min_uid: Option<u16>, max_uid: Option<u16>) -> (u16, u16) {
   if let (Some(min_uid), Some(max_uid)) = (min_uid, max_uid) {
     return (min_uid, max_uid);
@@ -250,31 +399,99 @@ pub fn get_min_max_uids(min_uid: Option<u16>, max_uid: Option<u16>) -> (u16, u16
   }
 }
 
+// Builds the list of directories to look for sessions into, from all provided
+// sources, in order of precedence. Directories appearing in several sources are
+// only kept once, at their first position.
+fn merge_session_paths(sources: &[&[(PathBuf, SessionType)]]) -> Vec<(PathBuf, SessionType)> {
+  let mut paths: Vec<(PathBuf, SessionType)> = vec![];
+
+  for (path, session_type) in sources.iter().flat_map(|source| source.iter()) {
+    if !paths.iter().any(|(existing, _)| existing == path) {
+      paths.push((path.clone(), *session_type));
+    }
+  }
+
+  paths
+}
+
+// Lists the directories sessions should be read from, along with the type of
+// the sessions they contain.
+pub fn get_session_paths(greeter: &Greeter) -> Vec<(PathBuf, SessionType)> {
+  let paths = merge_session_paths(&[&greeter.session_paths, &DEFAULT_SESSION_PATHS]);
+
+  match greeter.hide_x11_if_unavailable {
+    true => filter_x11_session_paths(paths, *XORG_AVAILABLE),
+    false => paths,
+  }
+}
+
 pub fn get_sessions(greeter: &Greeter) -> Result<Vec<Session>, Box<dyn Error>> {
-  let paths = if greeter.session_paths.is_empty() {
-    DEFAULT_SESSION_PATHS.as_ref()
-  } else {
-    &greeter.session_paths
-  };
+  let paths = get_session_paths(greeter);
 
   let mut files = vec![];
+  let depth = if greeter.sessions_recursive { SESSIONS_MAX_DEPTH } else { 0 };
 
   for (path, session_type) in paths.iter() {
     tracing::info!("reading {:?} sessions from '{}'", session_type, path.display());
 
-    if let Ok(entries) = fs::read_dir(path) {
-      files.extend(entries.flat_map(|entry| entry.map(|entry| load_desktop_file(entry.path(), *session_type))).flatten().flatten());
-    }
+    files.extend(read_sessions_from(path, *session_type, depth, greeter.xdg_env_name.as_deref()));
   }
 
   files.sort_by(|a, b| a.name.cmp(&b.name));
 
+  if greeter.show_session_last_used {
+    let last_used = read_sessions_last_used_from(LAST_SESSION);
+
+    for session in files.iter_mut() {
+      session.last_used = session.path.as_ref().and_then(|path| last_used.get(path)).copied();
+    }
+  }
+
   tracing::info!("found {} sessions", files.len());
 
   Ok(files)
 }
 
-fn load_desktop_file<P>(path: P, session_type: SessionType) -> Result<Option<Session>, Box<dyn Error>>
+// Loads all session files in a directory, descending into at most `depth`
+// levels of subdirectories.
+fn read_sessions_from(path: &Path, session_type: SessionType, depth: usize, env_name: Option<&str>) -> Vec<Session> {
+  let mut files = vec![];
+
+  if let Ok(entries) = fs::read_dir(path) {
+    for entry in entries.flatten() {
+      let path = entry.path();
+
+      if path.is_dir() {
+        if depth > 0 {
+          files.extend(read_sessions_from(&path, session_type, depth - 1, env_name));
+        }
+
+        continue;
+      }
+
+      if let Ok(Some(session)) = load_desktop_file(&path, session_type, env_name) {
+        files.push(session);
+      }
+    }
+  }
+
+  files
+}
+
+// Removes X11 session directories from the list of session paths, if X11
+// sessions cannot be started on this system.
+fn filter_x11_session_paths(paths: Vec<(PathBuf, SessionType)>, xorg_available: bool) -> Vec<(PathBuf, SessionType)> {
+  match xorg_available {
+    true => paths,
+    false => {
+      tracing::info!("Xorg was not found, hiding X11 sessions");
+
+      paths.into_iter().filter(|(_, session_type)| *session_type != SessionType::X11).collect()
+    }
+  }
+}
+
+fn load_desktop_file<P>(path: P, session_type: SessionType, env_name: Option<&str>) -> Result<Option<Session>, Box<dyn Error>>
 where
   P: AsRef<Path>,
 {
@@ -289,6 +506,14 @@ where
     tracing::info!("ignoring session in '{}': NoDisplay=true", path.as_ref().display());
     return Ok(None);
   }
+  // The greeter does not run in any desktop environment, so `NotShowIn` is only
+  // honored if we were told which name to match it against.
+  if let (Some(env_name), Some(not_show_in)) = (env_name, section.get("NotShowIn")) {
+    if not_show_in.split(';').any(|name| name == env_name) {
+      tracing::info!("ignoring session in '{}': NotShowIn={}", path.as_ref().display(), not_show_in);
+      return Ok(None);
+    }
+  }
 
   let slug = path.as_ref().file_stem().map(|slug| slug.to_string_lossy().to_string());
   let name = section.get("Name").ok_or("no Name property in desktop file")?;
@@ -304,10 +529,89 @@ where
     session_type,
     path: Some(path.as_ref().into()),
     xdg_desktop_names,
+    last_used: None,
   }))
 }
 
+// Finds when each session was last started, from the modification time of the
+// remembered session files, global and per-user, sharing the prefix of `path`.
+// Sessions that were never remembered are not returned.
+fn read_sessions_last_used_from<P>(path: P) -> HashMap<PathBuf, DateTime<Local>>
+where
+  P: AsRef<Path>,
+{
+  let mut sessions: HashMap<PathBuf, DateTime<Local>> = HashMap::new();
+
+  let (dir, prefix) = match (path.as_ref().parent(), path.as_ref().file_name()) {
+    (Some(dir), Some(prefix)) => (dir, prefix.to_string_lossy()),
+    _ => return sessions,
+  };
+
+  if let Ok(entries) = fs::read_dir(dir) {
+    for entry in entries.flatten() {
+      if !entry.file_name().to_string_lossy().starts_with(prefix.as_ref()) {
+        continue;
+      }
+
+      let modified = match entry.metadata().and_then(|metadata| metadata.modified()) {
+        Ok(modified) => DateTime::<Local>::from(modified),
+        Err(_) => continue,
+      };
+
+      if let Some(session) = read_cache_value(entry.path()) {
+        let last_used = sessions.entry(PathBuf::from(session)).or_insert(modified);
+
+        if modified > *last_used {
+          *last_used = modified;
+        }
+      }
+    }
+  }
+
+  sessions
+}
+
+// Returns the time of the last login of a user, as recorded in lastlog.
+pub fn get_last_login(username: &str) -> Option<DateTime<Local>> {
+  let uid = uzers::get_user_by_name(username)?.uid();
+
+  read_last_login_from(LASTLOG, uid)
+}
+
+// Records of lastlog are indexed by UID, and users who never logged in have a
+// zero timestamp.
+fn read_last_login_from<P>(path: P, uid: u32) -> Option<DateTime<Local>>
+where
+  P: AsRef<Path>,
+{
+  let mut file = File::open(path).ok()?;
+  let mut time = [0; 4];
+
+  file.seek(SeekFrom::Start(uid as u64 * LASTLOG_RECORD_SIZE)).ok()?;
+  file.read_exact(&mut time).ok()?;
+
+  match u32::from_ne_bytes(time) {
+    0 => None,
+    time => Local.timestamp_opt(time as i64, 0).single(),
+  }
+}
+
+// Returns whether caps lock is currently active.
+//
+// Since this is called on every render, `kbdinfo` is only spawned if it was
+// found on the system, and at most once every `CAPSLOCK_POLL_INTERVAL`.
 pub fn capslock_status() -> bool {
+  if !*KBDINFO_AVAILABLE {
+    return false;
+  }
+
+  match CAPSLOCK_STATUS.lock() {
+    Ok(mut status) => status.get(Instant::now(), probe_capslock),
+    Err(_) => false,
+  }
+}
+
+fn probe_capslock() -> bool {
   let mut command = Command::new("kbdinfo");
   command.args(["gkbled", "capslock"]);
 
@@ -317,6 +621,410 @@ pub fn capslock_status() -> bool {
   }
 }
 
+// Returns the space available to unprivileged users on the filesystem mounted
+// at the provided path, in bytes.
+pub fn get_free_space<P>(mountpoint: P) -> Option<u64>
+where
+  P: AsRef<Path>,
+{
+  match statvfs::statvfs(mountpoint.as_ref()) {
+    Ok(stats) => Some(stats.blocks_available() * stats.fragment_size()),
+
+    Err(err) => {
+      tracing::info!("could not get free space for '{}': {err}", mountpoint.as_ref().display());
+
+      None
+    }
+  }
+}
+
+// Whether the machine is connected to a network, which is assumed when the
+// kernel has a usable default IPv4 route.
+pub fn get_network_status() -> bool {
+  match fs::read_to_string("/proc/net/route") {
+    Ok(table) => has_default_route(&table),
+
+    Err(err) => {
+      tracing::info!("could not read the routing table: {err}");
+
+      false
+    }
+  }
+}
+
+// Looks for an active default route in the format of `/proc/net/route`: a
+// header line, then a route per line, with hexadecimal destination and flags.
+fn has_default_route(table: &str) -> bool {
+  table.lines().skip(1).any(|line| {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+
+    match (fields.get(1), fields.get(3).and_then(|flags| u16::from_str_radix(flags, 16).ok())) {
+      (Some(&"00000000"), Some(flags)) => flags & ROUTE_FLAG_UP != 0,
+      _ => false,
+    }
+  })
+}
+
+// Formats a size in bytes in a human-readable way, with binary units.
+pub fn format_size(bytes: u64) -> String {
+  const UNITS: [&str; 6] = ["B", "K", "M", "G", "T", "P"];
+
+  let mut size = bytes as f64;
+  let mut unit = 0;
+
+  while size >= 1024.0 && unit < UNITS.len() - 1 {
+    size /= 1024.0;
+    unit += 1;
+  }
+
+  match unit {
+    0 => format!("{bytes}{}", UNITS[0]),
+    _ => format!("{size:.1}{}", UNITS[unit]),
+  }
+}
+
+// Counts the sessions opened on the system by users other than the one running
+// the greeter, as reported by logind. Returns `None` if logind could not be
+// queried.
+pub fn get_active_sessions() -> Option<usize> {
+  let mut command = Command::new("loginctl");
+  command.args(["list-sessions", "--no-legend"]);
+
+  match command.output() {
+    Ok(output) if output.status.success() => Some(parse_loginctl_sessions(&String::from_utf8_lossy(&output.stdout), uzers::get_current_uid())),
+
+    Ok(_) | Err(_) => {
+      tracing::info!("could not list sessions from logind");
+
+      None
+    }
+  }
+}
+
+// Parses the output of `loginctl list-sessions --no-legend`, whose lines start
+// with the session ID and the UID of its owner.
+fn parse_loginctl_sessions(output: &str, greeter_uid: u32) -> usize {
+  output
+    .lines()
+    .filter_map(|line| line.split_whitespace().nth(1))
+    .filter_map(|uid| uid.parse::<u32>().ok())
+    .filter(|uid| *uid != greeter_uid)
+    .count()
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    cell::Cell,
+    fs::{self, File},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+  };
+
+  use chrono::{Local, TimeZone};
+
+  use crate::ui::{common::masked::MaskedString, messages_for, sessions::SessionType, users::User};
+
+  use super::{
+    filter_x11_session_paths, format_size, format_user_count, get_free_space, get_recent_users, has_default_route, is_in_path, merge_session_paths, parse_loginctl_sessions, read_cache_value,
+    read_last_login_from, read_sessions_from, read_sessions_last_used_from, write_audit, write_last_username_to, write_metrics, Throttle, LASTLOG_RECORD_SIZE, SESSIONS_MAX_DEPTH,
+  };
+
+  #[test]
+  fn free_space() {
+    assert!(get_free_space("/").is_some());
+    assert!(get_free_space("/nonexistent/mountpoint").is_none());
+  }
+
+  #[test]
+  fn human_readable_size() {
+    assert_eq!(format_size(0), "0B");
+    assert_eq!(format_size(1023), "1023B");
+    assert_eq!(format_size(1536), "1.5K");
+    assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0G");
+  }
+
+  #[test]
+  fn default_route() {
+    let header = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n";
+    let online = format!("{header}eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\neth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n");
+    let local = format!("{header}eth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n");
+    let down = format!("{header}eth0\t00000000\t0101A8C0\t0002\t0\t0\t100\t00000000\t0\t0\t0\n");
+
+    assert!(has_default_route(&online));
+    assert!(!has_default_route(&local));
+    assert!(!has_default_route(&down));
+    assert!(!has_default_route(header));
+    assert!(!has_default_route(""));
+  }
+
+  #[test]
+  fn loginctl_sessions() {
+    let output = "      1 1000 apognu  seat0 tty2\n     c1  967 greeter seat0 tty1\n      3 1001 bob     -     pts/0 \n";
+
+    assert_eq!(parse_loginctl_sessions(output, 967), 2);
+    assert_eq!(parse_loginctl_sessions(output, 1000), 2);
+    assert_eq!(parse_loginctl_sessions("", 967), 0);
+    assert_eq!(parse_loginctl_sessions("No sessions.\n", 967), 0);
+  }
+
+  #[test]
+  fn last_username_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let (username_path, name_path) = (dir.path().join("lastuser"), dir.path().join("lastuser-name"));
+
+    let table = [
+      (MaskedString::from("apognu".to_string(), Some("Antoine POPINEAU".to_string())), Some("apognu"), Some("Antoine POPINEAU")),
+      (
+        MaskedString::from("  apognu \n".to_string(), Some(" Antoine POPINEAU\n".to_string())),
+        Some("apognu"),
+        Some("Antoine POPINEAU"),
+      ),
+      (MaskedString::from("apognu".to_string(), None), Some("apognu"), None),
+      (MaskedString::from("apognu".to_string(), Some("  ".to_string())), Some("apognu"), None),
+      (MaskedString::from("   ".to_string(), None), None, None),
+    ];
+
+    for (username, expected_username, expected_name) in table {
+      write_last_username_to(&username_path, &name_path, &username);
+      let written = fs::read_to_string(&username_path).unwrap();
+
+      assert_eq!(written, written.trim());
+      assert_eq!(read_cache_value(&username_path).as_deref(), expected_username);
+      assert_eq!(read_cache_value(&name_path).as_deref(), expected_name);
+    }
+  }
+
+  #[test]
+  fn throttle_probes_once_per_interval() {
+    let calls = Cell::new(0);
+    let probe = || {
+      calls.set(calls.get() + 1);
+      calls.get()
+    };
+
+    let mut throttle = Throttle::new(Duration::from_secs(1));
+    let now = Instant::now();
+
+    assert_eq!(throttle.get(now, probe), 1);
+    assert_eq!(throttle.get(now + Duration::from_millis(500), probe), 1);
+    assert_eq!(throttle.get(now + Duration::from_millis(999), probe), 1);
+    assert_eq!(throttle.get(now + Duration::from_secs(1), probe), 2);
+    assert_eq!(calls.get(), 2);
+  }
+
+  #[test]
+  fn binary_in_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let binary = dir.path().join("kbdinfo");
+
+    assert!(!is_in_path(dir.path(), "kbdinfo"));
+
+    fs::write(&binary, "").unwrap();
+
+    assert!(!is_in_path(dir.path(), "kbdinfo"));
+
+    fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert!(is_in_path(dir.path(), "kbdinfo"));
+    assert!(is_in_path(std::env::join_paths(["/nonexistent".into(), dir.path().to_path_buf()]).unwrap(), "kbdinfo"));
+  }
+
+  #[test]
+  fn nested_session_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("flatpak").join("exports");
+
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(dir.path().join("sway.desktop"), "[Desktop Entry]\nName=Sway\nExec=sway\n").unwrap();
+    fs::write(nested.join("gnome.desktop"), "[Desktop Entry]\nName=GNOME\nExec=gnome-session\n").unwrap();
+
+    let sessions = read_sessions_from(dir.path(), SessionType::Wayland, 0, None);
+
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].name, "Sway");
+
+    let mut sessions = read_sessions_from(dir.path(), SessionType::Wayland, SESSIONS_MAX_DEPTH, None);
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].name, "GNOME");
+    assert_eq!(sessions[0].path.as_deref(), Some(nested.join("gnome.desktop").as_path()));
+    assert_eq!(sessions[1].name, "Sway");
+  }
+
+  #[test]
+  fn last_login_from_lastlog() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let mut lastlog = vec![0; 3 * LASTLOG_RECORD_SIZE as usize];
+
+    lastlog[2 * LASTLOG_RECORD_SIZE as usize..][..4].copy_from_slice(&1700000000u32.to_ne_bytes());
+    fs::write(file.path(), lastlog).unwrap();
+
+    assert_eq!(read_last_login_from(file.path(), 2).map(|time| time.timestamp()), Some(1700000000));
+    assert_eq!(read_last_login_from(file.path(), 1), None);
+    assert_eq!(read_last_login_from(file.path(), 1000), None);
+    assert_eq!(read_last_login_from("/nonexistent", 2), None);
+  }
+
+  #[test]
+  fn sessions_last_used_from_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("lastsession-path");
+    let time = |timestamp| SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp);
+
+    for (file, session, timestamp) in [
+      ("lastsession-path", "/usr/share/wayland-sessions/sway.desktop", 1700000000),
+      ("lastsession-path-apognu", "/usr/share/wayland-sessions/sway.desktop", 1700001000),
+      ("lastsession-path-bob", "/usr/share/xsessions/i3.desktop", 1600000000),
+      ("lastsession", "/usr/share/xsessions/xfce.desktop", 1700002000),
+    ]
+    .iter()
+    {
+      let path = dir.path().join(file);
+
+      fs::write(&path, session).unwrap();
+      File::options().write(true).open(&path).unwrap().set_modified(time(*timestamp)).unwrap();
+    }
+
+    let last_used = read_sessions_last_used_from(&cache);
+
+    assert_eq!(last_used.len(), 2);
+    assert_eq!(last_used.get(Path::new("/usr/share/wayland-sessions/sway.desktop")).map(|time| time.timestamp()), Some(1700001000));
+    assert_eq!(last_used.get(Path::new("/usr/share/xsessions/i3.desktop")).map(|time| time.timestamp()), Some(1600000000));
+    assert!(read_sessions_last_used_from("/nonexistent/lastsession-path").is_empty());
+  }
+
+  #[test]
+  fn metrics_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("metrics");
+
+    write_metrics(&path, "success", Duration::from_millis(1200));
+    write_metrics(&path, "failure", Duration::from_millis(30));
+    write_metrics(dir.path().join("nonexistent").join("metrics"), "failure", Duration::ZERO);
+
+    let metrics = fs::read_to_string(&path).unwrap();
+    let lines: Vec<Vec<&str>> = metrics.lines().map(|line| line.split(' ').collect()).collect();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0][1..], ["success", "1200"]);
+    assert_eq!(lines[1][1..], ["failure", "30"]);
+    assert!(chrono::DateTime::parse_from_rfc3339(lines[0][0]).is_ok());
+  }
+
+  #[test]
+  fn user_count() {
+    let english = messages_for("en-US");
+    let french = messages_for("fr-FR");
+
+    assert_eq!(format_user_count(&english, 1), "1 user");
+    assert_eq!(format_user_count(&english, 3), "3 users");
+    assert_eq!(format_user_count(&french, 0), "0 utilisateur");
+    assert_eq!(format_user_count(&french, 1), "1 utilisateur");
+    assert_eq!(format_user_count(&french, 3), "3 utilisateurs");
+  }
+
+  #[test]
+  fn audit_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit");
+
+    write_audit(&path, "apognu", SessionType::Wayland, "sway --unsupported-gpu");
+    write_audit(&path, "root", SessionType::None, "bash\nforged");
+
+    let audit = fs::read_to_string(&path).unwrap();
+    let lines: Vec<Vec<&str>> = audit.lines().map(|line| line.splitn(4, ' ').collect()).collect();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0][1..], ["apognu", "wayland", "sway --unsupported-gpu"]);
+    assert_eq!(lines[1][1..], ["root", "unspecified", "bash forged"]);
+    assert!(chrono::DateTime::parse_from_rfc3339(lines[0][0]).is_ok());
+  }
+
+  #[test]
+  fn recent_users() {
+    let users: Vec<User> = ["alice", "bob", "carol", "dave"]
+      .iter()
+      .map(|username| User {
+        username: username.to_string(),
+        name: None,
+      })
+      .collect();
+
+    let last_login = |username: &str| match username {
+      "alice" => Local.timestamp_opt(1600000000, 0).single(),
+      "bob" => Local.timestamp_opt(1700000000, 0).single(),
+      "dave" => Local.timestamp_opt(1650000000, 0).single(),
+      _ => None,
+    };
+
+    let usernames = |users: Vec<User>| users.into_iter().map(|user| user.username).collect::<Vec<_>>();
+
+    assert_eq!(usernames(get_recent_users(&users, 2, None, last_login)), vec!["bob", "dave"]);
+    assert_eq!(usernames(get_recent_users(&users, 10, None, last_login)), vec!["bob", "dave", "alice"]);
+    assert_eq!(usernames(get_recent_users(&users, 2, Some("carol"), last_login)), vec!["carol", "bob"]);
+    assert!(get_recent_users(&users, 2, None, |_| None).is_empty());
+  }
+
+  #[test]
+  fn not_show_in_session_files() {
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(dir.path().join("sway.desktop"), "[Desktop Entry]\nName=Sway\nExec=sway\nNotShowIn=GNOME;greeter;\n").unwrap();
+    fs::write(dir.path().join("gnome.desktop"), "[Desktop Entry]\nName=GNOME\nExec=gnome-session\nNotShowIn=KDE;\n").unwrap();
+
+    let sessions = read_sessions_from(dir.path(), SessionType::Wayland, 0, None);
+
+    assert_eq!(sessions.len(), 2);
+
+    let sessions = read_sessions_from(dir.path(), SessionType::Wayland, 0, Some("greeter"));
+
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].name, "GNOME");
+  }
+
+  #[test]
+  fn hide_x11_sessions_without_xorg() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = vec![
+      (PathBuf::from("/usr/share/wayland-sessions"), SessionType::Wayland),
+      (PathBuf::from("/usr/share/xsessions"), SessionType::X11),
+    ];
+
+    let filtered = filter_x11_session_paths(paths.clone(), is_in_path(dir.path(), "Xorg"));
+
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].1, SessionType::Wayland);
+
+    let binary = dir.path().join("Xorg");
+    fs::write(&binary, "").unwrap();
+    fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let filtered = filter_x11_session_paths(paths, is_in_path(dir.path(), "Xorg"));
+
+    assert_eq!(filtered.len(), 2);
+  }
+
+  #[test]
+  fn merge_session_paths_from_all_sources() {
+    let cli = vec![(PathBuf::from("/cli/wayland"), SessionType::Wayland), (PathBuf::from("/cli/x11"), SessionType::X11)];
+    let default = vec![
+      (PathBuf::from("/usr/share/wayland-sessions"), SessionType::Wayland),
+      (PathBuf::from("/cli/wayland"), SessionType::Wayland),
+    ];
+
+    let paths = merge_session_paths(&[&cli, &default]);
+
+    assert_eq!(
+      paths.iter().map(|(path, _)| path.to_str().unwrap()).collect::<Vec<_>>(),
+      vec!["/cli/wayland", "/cli/x11", "/usr/share/wayland-sessions"]
+    );
+    assert_eq!(paths[1].1, SessionType::X11);
+  }
+}
+
 #[cfg(feature = "nsswrapper")]
 #[cfg(test)]
 mod nsswrapper_tests {
@@ -332,4 +1040,15 @@ mod nsswrapper_tests {
     assert_eq!(users[1].username, "bob");
     assert_eq!(users[1].name, None);
   }
+
+  #[test]
+  fn nsswrapper_get_users_non_utf8() {
+    use super::get_users;
+
+    let users = get_users(1700, 2099);
+
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].username, "ana");
+    assert_eq!(users[0].name, Some("An\u{FFFD}".to_string()));
+  }
 }
@@ -1,4 +1,21 @@
+use std::{
+  path::PathBuf,
+  sync::atomic::{AtomicUsize, Ordering},
+  time::Duration,
+};
+
+use crossterm::event::{KeyCode, KeyModifiers};
+use greetd_ipc::{codec::TokioCodec, AuthMessageType, ErrorType, Request, Response};
 use libgreetd_stub::SessionOptions;
+use tokio::{net::UnixListener, time::timeout};
+
+use crate::{
+  ui::{
+    common::menu::Menu,
+    sessions::{Session, SessionSource},
+  },
+  PromptOrder,
+};
 
 use super::common::IntegrationRunner;
 
@@ -26,6 +43,351 @@ async fn authentication_ok() {
   runner.join_until_client_exit(events).await;
 }
 
+#[tokio::test]
+async fn password_first_prompt_order() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.prompt_order = PromptOrder::PasswordFirst;
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+      runner.wait_until_buffer_contains("Password:").await;
+
+      let output = runner.output().await;
+      let password = output.lines().position(|line| line.contains("Password:")).unwrap();
+      let username = output.lines().position(|line| line.contains("Username: apognu")).unwrap();
+
+      assert_eq!(username, password + 2);
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn recover_from_conflicting_session() {
+  let mut runner = IntegrationRunner::new_with_server(None, (200, 40), |socket| async move {
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+
+    // The first session creation fails as if one was already started, until
+    // tuigreet cancels it and reconnects.
+    let (mut stream, _) = listener.accept().await.unwrap();
+
+    if let Ok(Request::CreateSession { .. }) = Request::read_from(&mut stream).await {
+      let error = Response::Error {
+        error_type: ErrorType::Error,
+        description: "a session is already being configured".to_string(),
+      };
+
+      let _ = error.write_to(&mut stream).await;
+    }
+
+    let _ = Request::read_from(&mut stream).await;
+
+    let (mut stream, _) = listener.accept().await.unwrap();
+
+    if let Ok(Request::CreateSession { .. }) = Request::read_from(&mut stream).await {
+      let prompt = Response::AuthMessage {
+        auth_message_type: AuthMessageType::Secret,
+        auth_message: "Password:".to_string(),
+      };
+
+      let _ = prompt.write_to(&mut stream).await;
+    }
+
+    let _ = Request::read_from(&mut stream).await;
+  })
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Password:")).await.is_ok());
+      assert!(!runner.output().await.contains("An error was received from greetd"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn recover_from_conflicting_session_once() {
+  static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+  let mut runner = IntegrationRunner::new_with_server(None, (200, 40), |socket| async move {
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+
+    // greetd keeps reporting a conflicting session, whatever tuigreet does.
+    loop {
+      let (mut stream, _) = listener.accept().await.unwrap();
+
+      while let Ok(request) = Request::read_from(&mut stream).await {
+        match request {
+          Request::CreateSession { .. } => {
+            CREATED.fetch_add(1, Ordering::SeqCst);
+
+            let error = Response::Error {
+              error_type: ErrorType::Error,
+              description: "a session is already being configured".to_string(),
+            };
+
+            let _ = error.write_to(&mut stream).await;
+          }
+
+          Request::CancelSession => break,
+          _ => {}
+        }
+      }
+    }
+  })
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      // The session is recreated once, then the error is reported.
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("An error was received from greetd")).await.is_ok());
+      assert_eq!(CREATED.load(Ordering::SeqCst), 2);
+
+      // Submitting again does not start another recovery, since greetd never
+      // accepted a session in the meantime.
+      runner.send_text("apognu").await;
+
+      while CREATED.load(Ordering::SeqCst) < 3 {
+        runner.wait_for_render().await;
+      }
+
+      tokio::time::sleep(Duration::from_millis(300)).await;
+
+      assert_eq!(CREATED.load(Ordering::SeqCst), 3);
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn unrelated_error_is_not_a_conflict() {
+  let mut runner = IntegrationRunner::new_with_server(None, (200, 40), |socket| async move {
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+
+    // This error mentions something already happened, but is not about a
+    // session being configured, so it should be reported as is.
+    let (mut stream, _) = listener.accept().await.unwrap();
+
+    if let Ok(Request::CreateSession { .. }) = Request::read_from(&mut stream).await {
+      let error = Response::Error {
+        error_type: ErrorType::Error,
+        description: "user is already logged in".to_string(),
+      };
+
+      let _ = error.write_to(&mut stream).await;
+    }
+
+    let _ = listener.accept().await.unwrap();
+  })
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("An error was received from greetd")).await.is_ok());
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn recover_from_session_start_error() {
+  let mut runner = IntegrationRunner::new_with_server(None, (200, 40), |socket| async move {
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+
+    // Authentication succeeds, but the session cannot be started.
+    loop {
+      let (mut stream, _) = listener.accept().await.unwrap();
+
+      while let Ok(request) = Request::read_from(&mut stream).await {
+        let response = match request {
+          Request::CreateSession { .. } => Response::AuthMessage {
+            auth_message_type: AuthMessageType::Secret,
+            auth_message: "Password:".to_string(),
+          },
+
+          Request::PostAuthMessageResponse { .. } => Response::Success,
+
+          Request::StartSession { .. } => Response::Error {
+            error_type: ErrorType::Error,
+            description: "could not start session".to_string(),
+          },
+
+          Request::CancelSession => break,
+        };
+
+        let _ = response.write_to(&mut stream).await;
+      }
+    }
+  })
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+      runner.wait_until_buffer_contains("Password:").await;
+      runner.send_text("password").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("An error was received from greetd")).await.is_ok());
+
+      let output = runner.output().await;
+
+      assert!(output.contains("Username:"));
+      assert!(!output.contains("Please wait"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn username_validator() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.username_validator = Some("test apognu =".to_string());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("bob").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Unknown user")).await.is_ok());
+      assert!(!runner.output().await.contains("Password:"));
+
+      runner.send_modified_key(KeyCode::Char('u'), KeyModifiers::CONTROL).await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Password:")).await.is_ok());
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn prompt_override() {
+  let responses = vec![Response::AuthMessage {
+    auth_message_type: AuthMessageType::Secret,
+    auth_message: "Password: ".to_string(),
+  }];
+
+  let mut runner = IntegrationRunner::new_with_responses(
+    Some(|greeter| {
+      greeter.prompt_overrides = vec![("Password".to_string(), "Company password:".to_string())];
+    }),
+    responses,
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Company password:")).await.is_ok());
+      assert!(!runner.output().await.contains("Password:"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn scripted_multiple_prompts() {
+  let responses = vec![
+    Response::AuthMessage {
+      auth_message_type: AuthMessageType::Secret,
+      auth_message: "Password:".to_string(),
+    },
+    Response::AuthMessage {
+      auth_message_type: AuthMessageType::Visible,
+      auth_message: "Token:".to_string(),
+    },
+    Response::Error {
+      error_type: ErrorType::AuthError,
+      description: "".to_string(),
+    },
+  ];
+
+  let mut runner = IntegrationRunner::new_with_responses(None, responses).await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Password:")).await.is_ok());
+
+      runner.send_text("password").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Token:")).await.is_ok());
+
+      runner.send_text("123456").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Authentication failed")).await.is_ok());
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
 #[tokio::test]
 async fn authentication_bad_password() {
   let opts = SessionOptions {
@@ -55,6 +417,240 @@ async fn authentication_bad_password() {
   runner.join_until_end(events).await;
 }
 
+#[tokio::test]
+async fn authentication_bad_password_retry_message() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.retry_message = Some("Check caps lock and try again.".to_string());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+      runner.wait_until_buffer_contains("Password:").await;
+      runner.send_text("password2").await;
+      runner.wait_until_buffer_contains("Authentication failed").await;
+
+      assert!(runner.output().await.contains("Check caps lock and try again."));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn authentication_bad_password_once() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.once = true;
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+      runner.wait_until_buffer_contains("Password:").await;
+      runner.send_text("password2").await;
+    }
+  });
+
+  runner.join_until_client_exit(events).await;
+}
+
+fn metrics_file() -> PathBuf {
+  std::env::temp_dir().join(format!("tuigreet-metrics-{}", std::process::id()))
+}
+
+#[tokio::test]
+async fn authentication_metrics() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let _ = std::fs::remove_file(metrics_file());
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.metrics_file = Some(metrics_file());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+      runner.wait_until_buffer_contains("Password:").await;
+      runner.send_text("password2").await;
+      runner.wait_until_buffer_contains("Authentication failed").await;
+      runner.send_text("password").await;
+    }
+  });
+
+  runner.join_until_client_exit(events).await;
+
+  let metrics = std::fs::read_to_string(metrics_file()).unwrap();
+  let outcomes: Vec<&str> = metrics.lines().filter_map(|line| line.split(' ').nth(1)).collect();
+
+  let _ = std::fs::remove_file(metrics_file());
+
+  assert_eq!(outcomes, vec!["failure", "success"]);
+}
+
+fn audit_file() -> PathBuf {
+  std::env::temp_dir().join(format!("tuigreet-audit-{}", std::process::id()))
+}
+
+#[tokio::test]
+async fn authentication_audit() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let _ = std::fs::remove_file(audit_file());
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.audit_file = Some(audit_file());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+      runner.wait_until_buffer_contains("Password:").await;
+      runner.send_text("password").await;
+    }
+  });
+
+  runner.join_until_client_exit(events).await;
+
+  let audit = std::fs::read_to_string(audit_file()).unwrap();
+  let entries: Vec<Vec<&str>> = audit.lines().map(|line| line.splitn(4, ' ').skip(1).collect()).collect();
+
+  let _ = std::fs::remove_file(audit_file());
+
+  assert_eq!(entries, vec![vec!["apognu", "unspecified", "uname"]]);
+}
+
+fn guest_audit_file() -> PathBuf {
+  std::env::temp_dir().join(format!("tuigreet-guest-audit-{}", std::process::id()))
+}
+
+#[tokio::test]
+async fn authentication_guest() {
+  // greetd lets the guest in without asking for anything.
+  let responses = vec![Response::Success, Response::Success];
+
+  let _ = std::fs::remove_file(guest_audit_file());
+
+  let mut runner = IntegrationRunner::new_with_responses(
+    Some(|greeter| {
+      greeter.guest_user = Some("guest".to_string());
+      greeter.guest_command = Some("kiosk".to_string());
+      greeter.audit_file = Some(guest_audit_file());
+    }),
+    responses,
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("guest").await;
+    }
+  });
+
+  runner.join_until_client_exit(events).await;
+
+  let audit = std::fs::read_to_string(guest_audit_file()).unwrap();
+  let entries: Vec<Vec<&str>> = audit.lines().map(|line| line.splitn(4, ' ').skip(1).collect()).collect();
+
+  let _ = std::fs::remove_file(guest_audit_file());
+
+  assert_eq!(entries, vec![vec!["guest", "unspecified", "kiosk"]]);
+}
+
+#[tokio::test]
+async fn maintenance_session_preselected() {
+  // greetd lets the user in without asking for anything, but the preselected
+  // session is restricted to other users.
+  let responses = vec![Response::Success, Response::Success];
+
+  let runner = IntegrationRunner::new_with_responses(
+    Some(|greeter| {
+      greeter.maintenance_session = Some("rescue".to_string());
+      greeter.maintenance_session_users = vec!["admin".to_string()];
+      greeter.sessions = Menu::<Session> {
+        title: "List of sessions".to_string(),
+        options: vec![Session {
+          name: "Rescue".into(),
+          command: "rescue-shell".into(),
+          slug: Some("rescue".into()),
+          ..Default::default()
+        }],
+        selected: 0,
+        expand: None,
+      };
+      greeter.session_source = SessionSource::Session(0);
+    }),
+    responses,
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("This session is restricted")).await.is_ok());
+      assert!(runner.output().await.contains("Username:"));
+    }
+  });
+
+  // The session must not be started, so tuigreet is not expected to exit.
+  events.await.unwrap();
+}
+
 #[tokio::test]
 async fn authentication_ok_mfa() {
   let opts = SessionOptions {
@@ -2,15 +2,21 @@ mod backend;
 mod output;
 
 use std::{
+  collections::VecDeque,
+  env, fs,
+  future::Future,
   panic,
+  path::PathBuf,
   sync::{Arc, Mutex},
   time::Duration,
 };
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use greetd_ipc::{codec::TokioCodec, Request, Response};
 use libgreetd_stub::SessionOptions;
 use tempfile::NamedTempFile;
 use tokio::{
+  net::UnixListener,
   sync::{
     mpsc::{Receiver, Sender},
     RwLock,
@@ -21,6 +27,7 @@ use tui::buffer::Buffer;
 
 use crate::{
   event::{Event, Events},
+  info::get_hostname,
   ui::sessions::SessionSource,
   Greeter,
 };
@@ -53,19 +60,52 @@ impl IntegrationRunner {
   }
 
   pub async fn new_with_size(opts: SessionOptions, builder: Option<fn(&mut Greeter)>, size: (u16, u16)) -> IntegrationRunner {
+    IntegrationRunner::new_with_server(builder, size, |socket| async move {
+      libgreetd_stub::start(&socket, &opts).await;
+    })
+    .await
+  }
+
+  // Runs tuigreet against a server answering every request with the next of
+  // the provided responses, in order. Cancelling a session closes the
+  // connection, and requests are left unanswered once all responses were sent.
+  pub async fn new_with_responses(builder: Option<fn(&mut Greeter)>, responses: Vec<Response>) -> IntegrationRunner {
+    IntegrationRunner::new_with_server(builder, (200, 40), |socket| async move {
+      let _ = fs::remove_file(&socket);
+      let listener = UnixListener::bind(&socket).unwrap();
+      let mut responses = VecDeque::from(responses);
+
+      loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        while let Ok(request) = Request::read_from(&mut stream).await {
+          if let Request::CancelSession = request {
+            break;
+          }
+
+          if let Some(response) = responses.pop_front() {
+            let _ = response.write_to(&mut stream).await;
+          }
+        }
+      }
+    })
+    .await
+  }
+
+  // Runs tuigreet against a custom server instead of the greetd stub, for
+  // situations the stub cannot reproduce.
+  pub async fn new_with_server<F, Fut>(builder: Option<fn(&mut Greeter)>, size: (u16, u16), server: F) -> IntegrationRunner
+  where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+  {
     let socket = NamedTempFile::new().unwrap().into_temp_path().to_path_buf();
 
     let (backend, buffer, tick) = TestBackend::new(size.0, size.1);
     let events = Events::new().await;
     let sender = events.sender();
 
-    let server = tokio::task::spawn({
-      let socket = socket.clone();
-
-      async move {
-        libgreetd_stub::start(&socket, &opts).await;
-      }
-    });
+    let server = tokio::task::spawn(server(socket.clone()));
 
     let client = tokio::task::spawn(async move {
       let mut greeter = Greeter::new(events.sender()).await;
@@ -170,6 +210,53 @@ impl IntegrationRunner {
   pub async fn output(&self) -> Output {
     Output(output(&self.0.read().await.buffer))
   }
+
+  // Compares the whole rendered frame with the snapshot stored under
+  // `src/integration/snapshots`. Setting `TUIGREET_UPDATE_SNAPSHOTS` writes the
+  // current frame as the new snapshot instead.
+  #[allow(unused)]
+  pub async fn assert_snapshot(&self, name: &str) {
+    let frame = normalize_output(&self.output().await);
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/integration/snapshots").join(format!("{name}.snap"));
+
+    if env::var_os("TUIGREET_UPDATE_SNAPSHOTS").is_some() {
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(&path, &frame).unwrap();
+
+      return;
+    }
+
+    let snapshot = fs::read_to_string(&path).unwrap_or_else(|_| panic!("snapshot '{}' does not exist, run with TUIGREET_UPDATE_SNAPSHOTS=1 to create it", path.display()));
+
+    assert!(snapshot == frame, "frame does not match snapshot '{}':\n{frame}", path.display());
+  }
+}
+
+// Removes the parts of a rendered frame that depend on the machine running the
+// tests, so that it can be compared across environments. The hostname is
+// replaced by a placeholder, and the border that follows it in the title is
+// resized so that the frame keeps the same width.
+fn normalize_output(output: &str) -> String {
+  const PLACEHOLDER: &str = "<hostname>";
+
+  let title = fl!("title_authenticate", hostname = get_hostname());
+  let placeholder = fl!("title_authenticate", hostname = PLACEHOLDER);
+
+  output
+    .lines()
+    .map(|line| match line.find(&title) {
+      Some(start) => {
+        let (before, after) = (&line[..start], &line[start + title.len()..]);
+        let rest = after.trim_start_matches(' ').trim_start_matches('─');
+        let border = after[..after.len() - rest.len()].chars().count() as isize + title.chars().count() as isize - placeholder.chars().count() as isize;
+
+        format!("{before}{placeholder} {}{rest}", "─".repeat(border.max(1) as usize - 1)).trim_end().to_string()
+      }
+
+      _ => line.trim_end().to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
 }
 
 fn rethrow(result: Result<(), JoinError>) {
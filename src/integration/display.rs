@@ -3,6 +3,15 @@ use std::time::Duration;
 use chrono::Local;
 use libgreetd_stub::SessionOptions;
 
+use crate::{
+  info::get_hostname,
+  ui::{
+    common::masked::MaskedString,
+    sessions::{SessionSource, SessionType},
+  },
+  Greeter, Mode,
+};
+
 use super::common::IntegrationRunner;
 
 #[tokio::test]
@@ -34,6 +43,160 @@ async fn show_greet() {
   runner.join_until_end(events).await;
 }
 
+#[tokio::test]
+async fn username_screen_snapshot() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(opts, None).await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.assert_snapshot("username_screen").await;
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn show_footer() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.footer = Some("Welcome to %h, running tuigreet %v".to_string());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+
+      let footer = format!("Welcome to {}, running tuigreet {}", get_hostname(), env!("VERSION"));
+      let output = runner.output().await;
+      let status = output.lines().find(|line| line.contains("ESC")).unwrap();
+
+      assert!(status.starts_with(&format!("{footer} ESC")));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn status_separator() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.status_separator = Some(" | ".to_string());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+
+      let output = runner.output().await;
+      let status = output.lines().find(|line| line.contains("ESC")).unwrap();
+
+      assert!(status.starts_with("ESC Reset | F2 Change command | F3 Choose session | F12 Power | CMD uname"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn max_content_width() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new_with_size(
+    opts,
+    Some(|greeter| {
+      greeter.config = Greeter::options().parse(&["--max-content-width", "100"]).ok();
+      greeter.time = true;
+      greeter.time_format = Some("[CLOCK]".to_string());
+    }),
+    (400, 40),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+
+      let output = runner.output().await;
+      let status = output.lines().find(|line| line.contains("ESC")).unwrap();
+      let clock = output.lines().find(|line| line.contains("[CLOCK]")).unwrap();
+
+      assert_eq!(status.find("ESC"), Some(150));
+      assert_eq!(clock.find("[CLOCK]"), Some(197));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn show_username_and_name() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.show_username_and_name = true;
+      greeter.username = MaskedString::from("apognu".to_string(), Some("Antoine POPINEAU".to_string()));
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+
+      assert!(runner.output().await.contains("Username: Antoine POPINEAU (apognu)"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
 #[tokio::test]
 async fn show_wrapped_greet() {
   let opts = SessionOptions {
@@ -69,6 +232,230 @@ async fn show_wrapped_greet() {
   runner.join_until_end(events).await;
 }
 
+#[tokio::test]
+async fn show_session_starting_message() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.mode = Mode::Processing;
+      greeter.session_starting_message = Some("Starting %s...".to_string());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_for_render().await;
+
+      assert!(runner.output().await.contains("Starting uname..."));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn show_session_starting_message_guest_command() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.mode = Mode::Processing;
+      greeter.session_starting_message = Some("Starting %s...".to_string());
+      greeter.session_starting = Some(("kiosk".to_string(), SessionType::default()));
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_for_render().await;
+
+      // The command really started is named, not the selected session.
+      assert!(runner.output().await.contains("Starting kiosk..."));
+      assert!(!runner.output().await.contains("Starting uname"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn show_working_text() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.mode = Mode::Password;
+      greeter.prompt = Some("Password:".to_string());
+      greeter.working = true;
+      greeter.working_text = Some("Hang on...".to_string());
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_for_render().await;
+
+      let output = runner.output().await;
+
+      assert!(output.contains("Hang on..."));
+      assert!(!output.contains("Please wait..."));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn truncate_long_command() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.session_source = SessionSource::Command(format!("sway {} --end", "--verbose ".repeat(30)));
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_for_render().await;
+
+      let output = runner.output().await;
+      let status = output.lines().find(|line| line.contains("CMD")).unwrap();
+
+      assert!(status.trim_end().ends_with('…'));
+      assert!(!status.contains("--end"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn truncate_long_command_before_badges() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.session_source = SessionSource::Command(format!("sway {} --end", "--verbose ".repeat(30)));
+      greeter.show_session_count = true;
+      greeter.active_sessions = Some(2);
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_for_render().await;
+
+      let output = runner.output().await;
+      let status = output.lines().find(|line| line.contains("CMD")).unwrap();
+
+      assert!(status.contains('…'));
+      assert!(!status.contains("--end"));
+      assert!(status.contains("sessions"));
+      assert!(status.contains(&fl!("status_active_sessions", count = 2)));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn time_alignment() {
+  #[allow(clippy::type_complexity)]
+  let table: [(fn(&mut Greeter), usize); 3] = [
+    (
+      |greeter| {
+        greeter.config = Greeter::options().parse(&["--time-align", "left"]).ok();
+        greeter.time = true;
+        greeter.time_format = Some("[CLOCK]".to_string());
+      },
+      0,
+    ),
+    (
+      |greeter| {
+        greeter.config = Greeter::options().parse(&["--time-align", "center"]).ok();
+        greeter.time = true;
+        greeter.time_format = Some("[CLOCK]".to_string());
+      },
+      97,
+    ),
+    (
+      |greeter| {
+        greeter.config = Greeter::options().parse(&["--time-align", "right"]).ok();
+        greeter.time = true;
+        greeter.time_format = Some("[CLOCK]".to_string());
+      },
+      193,
+    ),
+  ];
+
+  for (builder, column) in table {
+    let opts = SessionOptions {
+      username: "apognu".to_string(),
+      password: "password".to_string(),
+      mfa: false,
+    };
+
+    let mut runner = IntegrationRunner::new(opts, Some(builder)).await;
+
+    let events = tokio::task::spawn({
+      let mut runner = runner.clone();
+
+      async move {
+        runner.wait_until_buffer_contains("[CLOCK]").await;
+
+        let output = runner.output().await;
+        let line = output.lines().find(|line| line.contains("[CLOCK]")).unwrap();
+
+        assert_eq!(line.find("[CLOCK]"), Some(column));
+      }
+    });
+
+    runner.join_until_end(events).await;
+  }
+}
+
 const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
 
 // TODO
@@ -83,7 +470,7 @@ async fn show_time() {
     mfa: false,
   };
 
-  let tref = Local::now().format(&TIME_FORMAT).to_string();
+  let tref = Local::now().format(TIME_FORMAT).to_string();
 
   let mut runner = IntegrationRunner::new(
     opts,
@@ -106,7 +493,7 @@ async fn show_time() {
 
       runner.wait_for_render().await;
 
-      assert_eq!(runner.output().await.contains(&tref), false);
+      assert!(!runner.output().await.contains(&tref));
     }
   });
 
@@ -1,5 +1,8 @@
+use std::time::Duration;
+
 use crossterm::event::{KeyCode, KeyModifiers};
 use libgreetd_stub::SessionOptions;
+use tokio::time::timeout;
 
 use crate::{
   power::PowerOption,
@@ -104,6 +107,86 @@ async fn change_command() {
   runner.join_until_end(events).await;
 }
 
+#[tokio::test]
+async fn power_menu_custom_title() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.power_title = Some("Goodbye?".to_string());
+      greeter.powers.title = "Goodbye?".to_string();
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_key(KeyCode::F(12)).await;
+      runner.wait_for_render().await;
+
+      assert!(runner.output().await.contains("Goodbye?"));
+      assert!(!runner.output().await.contains("Power options"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn require_session_selection() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.require_session_selection = true;
+      greeter.sessions = Menu::<Session> {
+        title: "List of sessions".to_string(),
+        options: vec![Session {
+          name: "My Session".to_string(),
+          ..Default::default()
+        }],
+        selected: 0,
+        expand: None,
+      };
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_text("apognu").await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Please choose a session with F3")).await.is_ok());
+
+      assert!(!runner.output().await.contains("Password:"));
+
+      runner.send_key(KeyCode::F(3)).await;
+      runner.send_key(KeyCode::Enter).await;
+      runner.send_key(KeyCode::Enter).await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Password:")).await.is_ok());
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
 #[tokio::test]
 async fn session_menu() {
   let opts = SessionOptions {
@@ -128,6 +211,7 @@ async fn session_menu() {
           },
         ],
         selected: 0,
+        expand: None,
       };
     }),
   )
@@ -192,6 +276,7 @@ async fn power_menu() {
           },
         ],
         selected: 0,
+        expand: None,
       };
     }),
   )
@@ -214,6 +299,55 @@ async fn power_menu() {
   runner.join_until_end(events).await;
 }
 
+#[tokio::test]
+async fn power_menu_commands() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.show_power_commands = true;
+      greeter.powers = Menu::<Power> {
+        title: "What to do?".to_string(),
+        options: vec![
+          Power {
+            action: PowerOption::Shutdown,
+            label: "Turn it off".to_string(),
+            ..Default::default()
+          },
+          Power {
+            action: PowerOption::Reboot,
+            label: "And back on again".to_string(),
+            command: Some("systemctl reboot".to_string()),
+          },
+        ],
+        selected: 0,
+        expand: None,
+      };
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_key(KeyCode::F(12)).await;
+      runner.wait_for_render().await;
+
+      assert!(runner.output().await.contains("(shutdown -h now)"));
+      assert!(runner.output().await.contains("(systemctl reboot)"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
 #[tokio::test]
 async fn users_menu() {
   let opts = SessionOptions {
@@ -239,6 +373,7 @@ async fn users_menu() {
           },
         ],
         selected: 0,
+        expand: None,
       }
     }),
   )
@@ -289,3 +424,111 @@ async fn users_menu() {
 
   runner.join_until_client_exit(events).await;
 }
+
+#[tokio::test]
+async fn session_count() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.show_session_count = true;
+      greeter.sessions = Menu::<Session> {
+        title: "List of sessions".to_string(),
+        options: vec![
+          Session {
+            name: "My Session".to_string(),
+            ..Default::default()
+          },
+          Session {
+            name: "Second Session".to_string(),
+            ..Default::default()
+          },
+        ],
+        selected: 0,
+        expand: None,
+      };
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+
+      assert!(runner.output().await.contains("CMD uname 2 sessions"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
+
+#[tokio::test]
+async fn session_menu_overflow() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.max_sessions_displayed = Some(2);
+      greeter.sessions = Menu::<Session> {
+        title: "List of sessions".to_string(),
+        options: ["First", "Second", "Third", "Fourth", "Fifth"]
+          .iter()
+          .map(|name| Session {
+            name: format!("{name} Session"),
+            ..Default::default()
+          })
+          .collect(),
+        selected: 0,
+        expand: None,
+      };
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username:").await;
+      runner.send_key(KeyCode::F(3)).await;
+      runner.wait_for_render().await;
+
+      let output = runner.output().await;
+
+      assert!(output.contains("First Session"));
+      assert!(output.contains("Second Session"));
+      assert!(!output.contains("Third Session"));
+      assert!(output.contains("… and 3 more"));
+
+      runner.send_key(KeyCode::Down).await;
+      runner.send_key(KeyCode::Down).await;
+      runner.wait_for_render().await;
+
+      let output = runner.output().await;
+
+      assert!(!output.contains("First Session"));
+      assert!(output.contains("Second Session"));
+      assert!(output.contains("Third Session"));
+      assert!(output.contains("… and 3 more"));
+
+      runner.send_key(KeyCode::Enter).await;
+      runner.wait_for_render().await;
+
+      assert!(runner.output().await.contains("SESS Third Session"));
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
@@ -1,5 +1,8 @@
+use std::time::Duration;
+
 use crossterm::event::KeyCode;
 use libgreetd_stub::SessionOptions;
+use tokio::time::timeout;
 
 use crate::ui::common::masked::MaskedString;
 
@@ -41,3 +44,38 @@ async fn remember_username() {
 
   runner.join_until_end(events).await;
 }
+
+#[tokio::test]
+async fn remember_username_without_auto_create_session() {
+  let opts = SessionOptions {
+    username: "apognu".to_string(),
+    password: "password".to_string(),
+    mfa: false,
+  };
+
+  let mut runner = IntegrationRunner::new(
+    opts,
+    Some(|greeter| {
+      greeter.remember = true;
+      greeter.auto_create_session = false;
+      greeter.username = MaskedString::from("apognu".to_string(), None);
+    }),
+  )
+  .await;
+
+  let events = tokio::task::spawn({
+    let mut runner = runner.clone();
+
+    async move {
+      runner.wait_until_buffer_contains("Username: apognu").await;
+
+      assert!(timeout(Duration::from_millis(500), runner.wait_until_buffer_contains("Password:")).await.is_err());
+
+      runner.send_key(KeyCode::Enter).await;
+
+      assert!(timeout(Duration::from_secs(2), runner.wait_until_buffer_contains("Password:")).await.is_ok());
+    }
+  });
+
+  runner.join_until_end(events).await;
+}
@@ -1,19 +1,99 @@
-use std::{borrow::Cow, error::Error, sync::Arc};
+use std::{
+  borrow::Cow,
+  error::Error,
+  io,
+  os::{linux::net::SocketAddrExt, unix::net::SocketAddr},
+  pin::Pin,
+  sync::Arc,
+  task::{Context, Poll},
+  time::{Duration, Instant},
+};
 
-use greetd_ipc::{codec::TokioCodec, AuthMessageType, ErrorType, Request, Response};
-use tokio::sync::{
-  mpsc::{Receiver, Sender},
-  Mutex, RwLock,
+use greetd_ipc::{
+  codec::{Error as CodecError, TokioCodec},
+  AuthMessageType, ErrorType, Request, Response,
+};
+use tokio::{
+  io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+  net::{TcpStream, UnixStream},
+  sync::{
+    mpsc::{Receiver, Sender},
+    Mutex, RwLock,
+  },
 };
 
 use crate::{
   event::Event,
-  info::{delete_last_user_command, delete_last_user_session, write_last_user_command, write_last_user_session, write_last_username},
+  info::{delete_last_user_command, delete_last_user_session, write_audit, write_last_user_command, write_last_user_session, write_last_username, write_metrics},
   macros::SafeDebug,
   ui::sessions::{Session, SessionSource, SessionType},
   AuthStatus, Greeter, Mode,
 };
 
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(250);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(8);
+
+// Connection to `greetd`, which usually listens on a Unix socket, but can also
+// be reached through an abstract socket or over TCP, for testing purposes.
+pub enum GreetdStream {
+  Unix(UnixStream),
+  Tcp(TcpStream),
+}
+
+impl GreetdStream {
+  // Connects to the provided address, which can either be:
+  //
+  //  - `tcp://HOST:PORT` for a TCP connection,
+  //  - a path starting with a null byte or `@` for an abstract socket,
+  //  - any other path for a regular Unix socket.
+  pub async fn connect(address: &str) -> io::Result<GreetdStream> {
+    if let Some(address) = address.strip_prefix("tcp://") {
+      return Ok(GreetdStream::Tcp(TcpStream::connect(address).await?));
+    }
+
+    if let Some(name) = address.strip_prefix('\0').or_else(|| address.strip_prefix('@')) {
+      let stream = std::os::unix::net::UnixStream::connect_addr(&SocketAddr::from_abstract_name(name)?)?;
+      stream.set_nonblocking(true)?;
+
+      return Ok(GreetdStream::Unix(UnixStream::from_std(stream)?));
+    }
+
+    Ok(GreetdStream::Unix(UnixStream::connect(address).await?))
+  }
+}
+
+impl AsyncRead for GreetdStream {
+  fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      GreetdStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+      GreetdStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+    }
+  }
+}
+
+impl AsyncWrite for GreetdStream {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    match self.get_mut() {
+      GreetdStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+      GreetdStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+    }
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      GreetdStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+      GreetdStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+    }
+  }
+
+  fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      GreetdStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+      GreetdStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct Ipc(Arc<IpcHandle>);
 
@@ -52,22 +132,87 @@ impl Ipc {
         greeter.stream.as_ref().unwrap().clone()
       };
 
-      let response = {
-        request.write_to(&mut *stream.write().await).await?;
+      // Authentication attempts are timed from the creation of their session.
+      if let Request::CreateSession { .. } = request {
+        greeter.write().await.auth_started = Some(Instant::now());
+      }
+
+      let response = match exchange(&stream, &request).await {
+        Ok(response) => response,
 
-        let response = Response::read_from(&mut *stream.write().await).await?;
+        // greetd closed the connection, most likely because it was restarted,
+        // and forgot about the session we were authenticating.
+        Err(err) if is_disconnected(&err) => {
+          tracing::error!("lost connection to greetd: {err}");
 
-        greeter.write().await.working = false;
+          self.reconnect(&greeter).await;
 
-        response
+          return Ok(());
+        }
+
+        Err(err) => return Err(err.into()),
       };
 
+      greeter.write().await.working = false;
+
       self.parse_response(&mut *greeter.write().await, response).await?;
     }
 
     Ok(())
   }
 
+  // Connects to greetd again, retrying with an increasing delay until it is back
+  // up, while letting the user know. Input is ignored in the meantime, and once
+  // connected, everything starts over.
+  async fn reconnect(&self, greeter: &Arc<RwLock<Greeter>>) {
+    let (socket, events) = {
+      let mut greeter = greeter.write().await;
+
+      greeter.set_message(fl!("reconnecting"));
+      greeter.working = true;
+
+      (greeter.socket.clone(), greeter.events.clone())
+    };
+
+    if let Some(ref events) = events {
+      let _ = events.send(Event::Render).await;
+    }
+
+    let mut delay = RECONNECT_INITIAL_DELAY;
+
+    let stream = loop {
+      match GreetdStream::connect(&socket).await {
+        Ok(stream) => break stream,
+
+        Err(err) => {
+          tracing::info!("could not reconnect to greetd, retrying in {}ms: {err}", delay.as_millis());
+
+          tokio::time::sleep(delay).await;
+
+          delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+      }
+    };
+
+    tracing::info!("reconnected to greetd");
+
+    {
+      let mut greeter = greeter.write().await;
+
+      // Anything queued before the connection was lost belonged to the previous
+      // session, which greetd does not know about anymore.
+      while self.0.rx.lock().await.try_recv().is_ok() {}
+
+      greeter.stream = Some(Arc::new(RwLock::new(stream)));
+      greeter.rewind(false);
+      greeter.set_message(fl!("reconnected"));
+    }
+
+    if let Some(ref events) = events {
+      let _ = events.send(Event::Render).await;
+    }
+  }
+
   async fn parse_response(&mut self, greeter: &mut Greeter, response: Response) -> Result<(), Box<dyn Error>> {
     // Do not display actual message from greetd, which may contain entered information, sometimes passwords.
     match response {
@@ -76,49 +221,63 @@ impl Ipc {
     }
 
     match response {
-      Response::AuthMessage { auth_message_type, auth_message } => match auth_message_type {
-        AuthMessageType::Secret => {
-          greeter.mode = Mode::Password;
-          greeter.working = false;
-          greeter.asking_for_secret = true;
-          greeter.set_prompt(&auth_message);
-        }
+      Response::AuthMessage { auth_message_type, auth_message } => {
+        // Only what is displayed is overridden, answers are still sent to greetd
+        // for the original message.
+        let auth_message = greeter.override_prompt(&auth_message).into_owned();
+
+        match auth_message_type {
+          AuthMessageType::Secret => {
+            greeter.mode = Mode::Password;
+            greeter.working = false;
+            greeter.asking_for_secret = true;
+            greeter.set_prompt(&auth_message);
+          }
 
-        AuthMessageType::Visible => {
-          greeter.mode = Mode::Password;
-          greeter.working = false;
-          greeter.asking_for_secret = false;
-          greeter.set_prompt(&auth_message);
-        }
+          AuthMessageType::Visible => {
+            greeter.mode = Mode::Password;
+            greeter.working = false;
+            greeter.asking_for_secret = false;
+            greeter.set_prompt(&auth_message);
+          }
 
-        AuthMessageType::Error => {
-          greeter.message = Some(auth_message);
+          AuthMessageType::Error => {
+            greeter.set_error(auth_message);
 
-          self.send(Request::PostAuthMessageResponse { response: None }).await;
-        }
+            self.send(Request::PostAuthMessageResponse { response: None }).await;
+          }
 
-        AuthMessageType::Info => {
-          greeter.remove_prompt();
+          AuthMessageType::Info => {
+            greeter.remove_prompt();
 
-          greeter.previous_mode = greeter.mode;
-          greeter.mode = Mode::Action;
+            greeter.previous_mode = greeter.mode;
+            greeter.mode = Mode::Action;
 
-          if let Some(message) = &mut greeter.message {
-            message.push('\n');
-            message.push_str(auth_message.trim_end());
-          } else {
-            greeter.message = Some(auth_message.trim_end().to_string());
-          }
+            let message = match greeter.message.take() {
+              Some(message) => format!("{message}\n{}", auth_message.trim_end()),
+              None => auth_message.trim_end().to_string(),
+            };
 
-          self.send(Request::PostAuthMessageResponse { response: None }).await;
+            greeter.set_message(message);
+
+            self.send(Request::PostAuthMessageResponse { response: None }).await;
+          }
         }
-      },
+      }
 
       Response::Success => {
+        // greetd accepted the session, so a later conflict can be recovered
+        // from again.
+        greeter.session_recreated = false;
+
         if greeter.done {
           tracing::info!("greetd acknowledged session start, exiting");
 
-          if greeter.remember {
+          record_session_start(greeter);
+
+          if greeter.remember && greeter.is_remember_excluded() {
+            tracing::info!("not caching anything for excluded user {}", greeter.username.value);
+          } else if greeter.remember {
             tracing::info!("caching last successful username");
 
             write_last_username(&greeter.username);
@@ -152,18 +311,39 @@ impl Ipc {
         } else {
           tracing::info!("authentication successful, starting session");
 
-          match greeter.session_source.command(greeter).map(str::to_string) {
+          record_auth_outcome(greeter, "success");
+
+          // The guest user is always given the configured guest command.
+          let guest_command = greeter.guest_command.clone().filter(|_| greeter.is_guest());
+          let command = match guest_command {
+            Some(ref command) => Some(command.clone()),
+            None => greeter.session_source.command(greeter).map(str::to_string),
+          };
+
+          match command {
             None => {
               Ipc::cancel(greeter).await;
 
-              greeter.message = Some(fl!("command_missing"));
+              greeter.set_error(fl!("command_missing"));
               greeter.reset(false).await;
             }
 
             Some(command) if command.is_empty() => {
               Ipc::cancel(greeter).await;
 
-              greeter.message = Some(fl!("command_missing"));
+              greeter.set_error(fl!("command_missing"));
+              greeter.reset(false).await;
+            }
+
+            // The selected session can come from the defaults or the remember
+            // cache without going through the menu, so this is checked again
+            // right before starting it.
+            Some(_) if guest_command.is_none() && Session::get_selected(greeter).map(|session| !greeter.is_session_allowed(session)).unwrap_or(false) => {
+              tracing::info!("user {} is not allowed to start the maintenance session", greeter.username.value);
+
+              Ipc::cancel(greeter).await;
+
+              greeter.set_error(fl!("maintenance_session_denied"));
               greeter.reset(false).await;
             }
 
@@ -171,10 +351,17 @@ impl Ipc {
               greeter.done = true;
               greeter.mode = Mode::Processing;
 
-              let session = Session::get_selected(greeter);
-              let default = DefaultCommand(&command, greeter.session_source.env());
+              let (session, env) = match guest_command {
+                Some(_) => (None, None),
+                None => (Session::get_selected(greeter), greeter.session_source.env()),
+              };
+
+              let default = DefaultCommand(&command, env);
+              let session_type = session.map(|session| session.session_type).unwrap_or_default();
               let (command, env) = wrap_session_command(greeter, session, &default);
 
+              greeter.session_starting = Some((command.to_string(), session_type));
+
               #[cfg(not(debug_assertions))]
               self.send(Request::StartSession { cmd: vec![command.to_string()], env }).await;
 
@@ -189,15 +376,43 @@ impl Ipc {
         }
       }
 
-      Response::Error { error_type, .. } => {
+      Response::Error { error_type, description } => {
         // Do not display actual message from greetd, which may contain entered information, sometimes passwords.
         tracing::info!("received an error from greetd: {error_type:?}");
 
         Ipc::cancel(greeter).await;
 
+        if let ErrorType::AuthError = error_type {
+          record_auth_outcome(greeter, "failure");
+        }
+
         match error_type {
+          // greetd still has a session being configured, probably because
+          // tuigreet was restarted halfway through authentication. Now that it
+          // was cancelled, we can start over with the same user, once until
+          // greetd accepts a session again.
+          ErrorType::Error if is_session_conflict(&description) && !greeter.session_recreated => {
+            tracing::info!("greetd reported a conflicting session, recreating it");
+
+            greeter.session_recreated = true;
+            self
+              .send(Request::CreateSession {
+                username: greeter.username.value.clone(),
+              })
+              .await;
+            greeter.reset(true).await;
+          }
+
+          ErrorType::AuthError if greeter.once => {
+            tracing::info!("authentication failed, exiting");
+
+            if let Some(ref sender) = greeter.events {
+              let _ = sender.send(Event::Exit(AuthStatus::Failure)).await;
+            }
+          }
+
           ErrorType::AuthError => {
-            greeter.message = Some(fl!("failed"));
+            greeter.set_error(greeter.failure_message());
             self
               .send(Request::CreateSession {
                 username: greeter.username.value.clone(),
@@ -208,7 +423,7 @@ impl Ipc {
 
           ErrorType::Error => {
             // Do not display actual message from greetd, which may contain entered information, sometimes passwords.
-            greeter.message = Some("An error was received from greetd".to_string());
+            greeter.set_error("An error was received from greetd".to_string());
             greeter.reset(false).await;
           }
         }
@@ -225,6 +440,66 @@ impl Ipc {
   }
 }
 
+// Sends a request to greetd and reads its response. Messages are framed here
+// and only serialized by the codec, so that I/O errors keep their kind.
+async fn exchange(stream: &RwLock<GreetdStream>, request: &Request) -> io::Result<Response> {
+  let mut stream = stream.write().await;
+
+  let mut frame = Vec::new();
+  request.write_to(&mut frame).await.map_err(invalid_data)?;
+  stream.write_all(&frame).await?;
+
+  let mut len = [0; 4];
+  stream.read_exact(&mut len).await?;
+
+  let mut frame = len.to_vec();
+  frame.resize(4 + u32::from_ne_bytes(len) as usize, 0);
+  stream.read_exact(&mut frame[4..]).await?;
+
+  Response::read_from(&mut frame.as_slice()).await.map_err(invalid_data)
+}
+
+fn invalid_data(err: CodecError) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+// Whether an error while talking to greetd means the connection is gone.
+fn is_disconnected(err: &io::Error) -> bool {
+  matches!(
+    err.kind(),
+    io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::UnexpectedEof | io::ErrorKind::NotConnected
+  )
+}
+
+// Appends the outcome of the current authentication attempt to the metrics
+// file, if one was configured.
+fn record_auth_outcome(greeter: &mut Greeter, outcome: &str) {
+  if let Some(ref path) = greeter.metrics_file {
+    let duration = greeter.auth_started.take().map(|started| started.elapsed()).unwrap_or_default();
+
+    write_metrics(path, outcome, duration);
+  }
+}
+
+// Appends the session greetd just started to the audit file, if one was
+// configured. Only the command is recorded, since its environment may hold
+// tokens.
+fn record_session_start(greeter: &mut Greeter) {
+  if let (Some(path), Some((command, session_type))) = (&greeter.audit_file, greeter.session_starting.take()) {
+    write_audit(path, &greeter.username.value, session_type, &command);
+  }
+}
+
+// Error greetd returns when a session is created while another one is still
+// being configured.
+const SESSION_CONFLICT_ERROR: &str = "a session is already being configured";
+
+// Whether an error returned by greetd means a session was already being
+// configured, which is cleared by cancelling it.
+fn is_session_conflict(description: &str) -> bool {
+  description == SESSION_CONFLICT_ERROR
+}
+
 fn desktop_names_to_xdg(names: &str) -> String {
   names.replace(';', ":").trim_end_matches(':').to_string()
 }
@@ -244,7 +519,11 @@ impl<'a> DefaultCommand<'a> {
 fn wrap_session_command<'a>(greeter: &Greeter, session: Option<&Session>, default: &'a DefaultCommand<'a>) -> (Cow<'a, str>, Vec<String>) {
   let mut env: Vec<String> = vec![];
 
-  match session {
+  if let Some(ref token) = greeter.activation_token {
+    env.push(format!("XDG_ACTIVATION_TOKEN={token}"));
+  }
+
+  let command = match session {
     // If the target is a defined session, we should be able to deduce all the
     // environment we need from the desktop file.
     Some(Session {
@@ -260,16 +539,22 @@ fn wrap_session_command<'a>(greeter: &Greeter, session: Option<&Session>, defaul
       if *session_type != SessionType::None {
         env.push(format!("XDG_SESSION_TYPE={}", session_type.as_xdg_session_type()));
       }
-      if let Some(xdg_desktop_names) = xdg_desktop_names {
+      // Desktop names provided on the command line take precedence over the
+      // ones from the session file, which may be missing or wrong.
+      let desktop_names_override = slug
+        .as_ref()
+        .and_then(|slug| greeter.session_desktop_names.iter().find(|(name, _)| name == slug))
+        .map(|(_, names)| names);
+
+      if let Some(xdg_desktop_names) = desktop_names_override.or(xdg_desktop_names.as_ref()) {
         env.push(format!("XDG_CURRENT_DESKTOP={}", desktop_names_to_xdg(xdg_desktop_names)));
       }
 
-      if *session_type == SessionType::X11 {
-        if let Some(ref wrap) = greeter.xsession_wrapper {
-          return (Cow::Owned(format!("{} {}", wrap, default.command())), env);
-        }
-      } else if let Some(ref wrap) = greeter.session_wrapper {
-        return (Cow::Owned(format!("{} {}", wrap, default.command())), env);
+      match (session_type, &greeter.xsession_wrapper, &greeter.session_wrapper) {
+        (SessionType::X11, Some(wrap), _) => Cow::Owned(format!("{} {}", wrap, default.command())),
+        (SessionType::X11, None, _) => Cow::Borrowed(default.command()),
+        (_, _, Some(wrap)) => Cow::Owned(format!("{} {}", wrap, default.command())),
+        (_, _, None) => Cow::Borrowed(default.command()),
       }
     }
 
@@ -277,30 +562,152 @@ fn wrap_session_command<'a>(greeter: &Greeter, session: Option<&Session>, defaul
       // If a wrapper script is used, assume that it is able to set up the
       // required environment.
       if let Some(ref wrap) = greeter.session_wrapper {
-        return (Cow::Owned(format!("{} {}", wrap, default.command())), env);
-      }
-      // Otherwise, set up the environment from the provided argument.
-      if let Some(base_env) = default.env() {
-        env.append(&mut base_env.clone());
+        Cow::Owned(format!("{} {}", wrap, default.command()))
+      } else {
+        // Otherwise, set up the environment from the provided argument.
+        if let Some(base_env) = default.env() {
+          env.append(&mut base_env.clone());
+        }
+
+        Cow::Borrowed(default.command())
       }
     }
-  }
+  };
+
+  // Whatever their source, stripped variables must never reach the session.
+  env.retain(|definition| {
+    let name = definition.split('=').next().unwrap_or_default();
 
-  (Cow::Borrowed(default.command()), env)
+    !greeter.strip_env.iter().any(|stripped| stripped == name)
+  });
+
+  (command, env)
 }
 
 #[cfg(test)]
 mod test {
-  use std::path::PathBuf;
+  use std::{path::PathBuf, sync::Arc, time::Duration};
+
+  use greetd_ipc::Request;
+  use tokio::{net::UnixListener, sync::RwLock};
 
   use crate::{
-    ipc::{desktop_names_to_xdg, DefaultCommand},
+    ipc::{desktop_names_to_xdg, is_disconnected, is_session_conflict, DefaultCommand, GreetdStream, Ipc},
     ui::sessions::{Session, SessionType},
-    Greeter,
+    Greeter, Mode,
   };
 
   use super::wrap_session_command;
 
+  #[test]
+  fn session_conflict_errors() {
+    assert!(is_session_conflict("a session is already being configured"));
+    assert!(!is_session_conflict("Invalid credentials"));
+    assert!(!is_session_conflict("user is already logged in"));
+    assert!(!is_session_conflict("pam_unix: account already locked"));
+  }
+
+  #[test]
+  fn disconnection_errors() {
+    use std::io::{Error, ErrorKind};
+
+    assert!(is_disconnected(&Error::from(ErrorKind::BrokenPipe)));
+    assert!(is_disconnected(&Error::from(ErrorKind::ConnectionReset)));
+    assert!(is_disconnected(&Error::from(ErrorKind::UnexpectedEof)));
+    assert!(is_disconnected(&Error::from(ErrorKind::NotConnected)));
+    assert!(!is_disconnected(&Error::from(ErrorKind::InvalidData)));
+    assert!(!is_disconnected(&Error::from(ErrorKind::PermissionDenied)));
+  }
+
+  #[tokio::test]
+  async fn reconnect_after_greetd_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("greetd.sock");
+
+    // The first connection is dropped along with the socket, which only comes
+    // back a little later, as if greetd was restarted.
+    let server = tokio::task::spawn({
+      let socket = socket.clone();
+
+      async move {
+        let listener = UnixListener::bind(&socket).unwrap();
+        let _ = listener.accept().await.unwrap();
+
+        drop(listener);
+        std::fs::remove_file(&socket).unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+
+        let listener = UnixListener::bind(&socket).unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        stream
+      }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+    let ipc = Ipc::new();
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.socket = socket.to_string_lossy().to_string();
+      greeter.mode = Mode::Password;
+      greeter.working = true;
+      greeter.connect().await;
+    }
+
+    ipc.send(Request::CreateSession { username: "apognu".into() }).await;
+
+    let handle = tokio::task::spawn({
+      let greeter = greeter.clone();
+      let mut ipc = ipc.clone();
+
+      async move { ipc.handle(greeter).await.is_ok() }
+    });
+
+    // While greetd is away, the greeter stays busy, and whatever gets queued is
+    // dropped once the connection is back.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(greeter.read().await.working);
+    assert_eq!(greeter.read().await.message, Some(fl!("reconnecting")));
+
+    ipc.send(Request::CreateSession { username: "apognu".into() }).await;
+
+    assert!(handle.await.unwrap());
+    assert!(server.await.is_ok());
+    assert!(ipc.0.rx.lock().await.try_recv().is_err());
+
+    let greeter = greeter.read().await;
+
+    assert_eq!(greeter.mode, Mode::Username);
+    assert!(!greeter.working);
+    assert_eq!(greeter.message, Some(fl!("reconnected")));
+  }
+
+  #[tokio::test]
+  async fn connect_abstract_socket() {
+    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+    let name = format!("tuigreet-test-{}", std::process::id());
+    let listener = std::os::unix::net::UnixListener::bind_addr(&SocketAddr::from_abstract_name(&name).unwrap()).unwrap();
+
+    assert!(GreetdStream::connect(&format!("@{name}")).await.is_ok());
+    assert!(GreetdStream::connect(&format!("\0{name}")).await.is_ok());
+    assert!(GreetdStream::connect("@tuigreet-test-nonexistent").await.is_err());
+
+    drop(listener);
+  }
+
+  #[tokio::test]
+  async fn connect_tcp_socket() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    assert!(matches!(GreetdStream::connect(&format!("tcp://{address}")).await, Ok(GreetdStream::Tcp(_))));
+  }
+
   #[test]
   fn wayland_no_wrapper() {
     let greeter = Greeter::default();
@@ -340,6 +747,84 @@ mod test {
     assert_eq!(env, vec!["XDG_SESSION_TYPE=wayland"]);
   }
 
+  #[test]
+  fn wayland_activation_token() {
+    let mut greeter = Greeter::default();
+    greeter.activation_token = Some("mytoken".into());
+
+    let session = Session {
+      name: "Session1".into(),
+      session_type: SessionType::Wayland,
+      command: "Session1Cmd".into(),
+      path: Some(PathBuf::from("/Session1Path")),
+      ..Default::default()
+    };
+
+    let default = DefaultCommand(&session.command, None);
+    let (command, env) = wrap_session_command(&greeter, Some(&session), &default);
+
+    assert_eq!(command.as_ref(), "Session1Cmd");
+    assert_eq!(env, vec!["XDG_ACTIVATION_TOKEN=mytoken", "XDG_SESSION_TYPE=wayland"]);
+  }
+
+  #[test]
+  fn desktop_names_override() {
+    let mut greeter = Greeter::default();
+    greeter.session_desktop_names = vec![("sway".into(), "sway:wlroots".into())];
+
+    let session = |slug: &str| Session {
+      slug: Some(slug.into()),
+      session_type: SessionType::Wayland,
+      command: "sway".into(),
+      xdg_desktop_names: Some("Sway;".into()),
+      ..Default::default()
+    };
+
+    let sway = session("sway");
+    let default = DefaultCommand(&sway.command, None);
+    let (_, env) = wrap_session_command(&greeter, Some(&sway), &default);
+
+    assert!(env.contains(&"XDG_CURRENT_DESKTOP=sway:wlroots".to_string()));
+
+    let other = session("sway-nvidia");
+    let (_, env) = wrap_session_command(&greeter, Some(&other), &default);
+
+    assert!(env.contains(&"XDG_CURRENT_DESKTOP=Sway".to_string()));
+
+    let mut bare = session("sway");
+    bare.xdg_desktop_names = None;
+    let (_, env) = wrap_session_command(&greeter, Some(&bare), &default);
+
+    assert!(env.contains(&"XDG_CURRENT_DESKTOP=sway:wlroots".to_string()));
+  }
+
+  #[test]
+  fn strip_env() {
+    let mut greeter = Greeter::default();
+    greeter.activation_token = Some("mytoken".into());
+    greeter.strip_env = vec!["XDG_ACTIVATION_TOKEN".into(), "SECRET".into()];
+
+    let default = DefaultCommand("sway", Some(vec!["SECRET=hunter2".into(), "SECRETS=kept".into(), "A=B".into()]));
+    let (command, env) = wrap_session_command(&greeter, None, &default);
+
+    assert_eq!(command.as_ref(), "sway");
+    assert_eq!(env, vec!["SECRETS=kept", "A=B"]);
+
+    let session = Session {
+      slug: Some("sway".into()),
+      session_type: SessionType::Wayland,
+      command: "sway".into(),
+      ..Default::default()
+    };
+
+    greeter.strip_env = vec!["DESKTOP_SESSION".into()];
+
+    let default = DefaultCommand(&session.command, None);
+    let (_, env) = wrap_session_command(&greeter, Some(&session), &default);
+
+    assert_eq!(env, vec!["XDG_ACTIVATION_TOKEN=mytoken", "XDG_SESSION_DESKTOP=sway", "XDG_SESSION_TYPE=wayland"]);
+  }
+
   #[test]
   fn x11_wrapper() {
     let mut greeter = Greeter::default();
@@ -352,7 +837,7 @@ mod test {
       command: "Session1Cmd".into(),
       path: Some(PathBuf::from("/Session1Path")),
       xdg_desktop_names: Some("one;two;three;".to_string()),
-      ..Default::default()
+      last_used: None,
     };
 
     let default = DefaultCommand(&session.command, None);
@@ -1,21 +1,27 @@
-use std::{error::Error, sync::Arc};
+use std::{error::Error, process::Stdio, sync::Arc, time::Duration};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use greetd_ipc::Request;
-use tokio::sync::RwLock;
+use tokio::{process::Command, sync::RwLock, time::timeout};
 
 use crate::{
-  info::{delete_last_command, delete_last_session, get_last_user_command, get_last_user_session, write_last_command, write_last_session_path},
+  event::Event,
+  info::{delete_last_command, delete_last_session, get_last_login, get_last_user_command, get_last_user_session, write_last_command, write_last_session_path},
   ipc::Ipc,
   power::power,
   ui::{
-    common::masked::MaskedString,
+    common::{
+      masked::MaskedString,
+      menu::{Menu, MenuItem},
+    },
     sessions::{Session, SessionSource},
     users::User,
   },
-  Greeter, Mode,
+  Greeter, GreetingRotation, Mode,
 };
 
+const USERNAME_VALIDATOR_TIMEOUT: Duration = Duration::from_secs(3);
+
 // Act on keyboard events.
 //
 // This function will be called whenever a keyboard event was captured by the
@@ -29,6 +35,10 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
     return Ok(());
   }
 
+  if greeter.greeting_rotate == GreetingRotation::Keypress {
+    greeter.rotate_greeting();
+  }
+
   match input {
     // ^U should erase the current buffer.
     KeyEvent {
@@ -49,7 +59,7 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
       modifiers: KeyModifiers::CONTROL,
       ..
     } => {
-      use crate::{AuthStatus, Event};
+      use crate::AuthStatus;
 
       if let Some(ref sender) = greeter.events {
         let _ = sender.send(Event::Exit(AuthStatus::Cancel)).await;
@@ -80,87 +90,29 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
     KeyEvent { code: KeyCode::Left, .. } => greeter.cursor_offset -= 1,
     KeyEvent { code: KeyCode::Right, .. } => greeter.cursor_offset += 1,
 
-    // F2 will display the command entry prompt. If we are already in one of the
-    // popup screens, we set the previous screen as being the current previous
-    // screen.
+    // F2 will display the command entry prompt.
     KeyEvent { code: KeyCode::F(i), .. } if i == greeter.kb_command => {
-      greeter.previous_mode = match greeter.mode {
-        Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
-        _ => greeter.mode,
-      };
-
       // Set the edition buffer to the current command.
       greeter.previous_buffer = Some(greeter.buffer.clone());
       greeter.buffer = greeter.session_source.command(&greeter).map(str::to_string).unwrap_or_default();
       greeter.cursor_offset = 0;
-      greeter.mode = Mode::Command;
+      greeter.enter_transient_mode(Mode::Command);
     }
 
-    // F3 will display the session selection menu. If we are already in one of
-    // the popup screens, we set the previous screen as being the current
-    // previous screen.
-    KeyEvent { code: KeyCode::F(i), .. } if i == greeter.kb_sessions => {
-      greeter.previous_mode = match greeter.mode {
-        Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
-        _ => greeter.mode,
-      };
-
-      greeter.mode = Mode::Sessions;
-    }
+    // F3 will switch to the next session tab, if they are displayed.
+    KeyEvent { code: KeyCode::F(i), .. } if i == greeter.kb_sessions && greeter.show_session_tabs() => cycle_session_tabs(&mut greeter),
 
-    // F12 will display the user selection menu. If we are already in one of the
-    // popup screens, we set the previous screen as being the current previous
-    // screen.
-    KeyEvent { code: KeyCode::F(i), .. } if i == greeter.kb_power => {
-      greeter.previous_mode = match greeter.mode {
-        Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
-        _ => greeter.mode,
-      };
+    // F3 will display the session selection menu.
+    KeyEvent { code: KeyCode::F(i), .. } if i == greeter.kb_sessions => greeter.enter_transient_mode(Mode::Sessions),
 
-      greeter.mode = Mode::Power;
-    }
+    // F12 will display the power menu.
+    KeyEvent { code: KeyCode::F(i), .. } if i == greeter.kb_power => greeter.enter_transient_mode(Mode::Power),
 
     // Handle moving up in menus.
-    KeyEvent { code: KeyCode::Up, .. } => {
-      if let Mode::Users = greeter.mode {
-        if greeter.users.selected > 0 {
-          greeter.users.selected -= 1;
-        }
-      }
-
-      if let Mode::Sessions = greeter.mode {
-        if greeter.sessions.selected > 0 {
-          greeter.sessions.selected -= 1;
-        }
-      }
-
-      if let Mode::Power = greeter.mode {
-        if greeter.powers.selected > 0 {
-          greeter.powers.selected -= 1;
-        }
-      }
-    }
+    KeyEvent { code: KeyCode::Up, .. } => navigate_menu(&mut greeter, KeyCode::Up),
 
     // Handle moving down in menus.
-    KeyEvent { code: KeyCode::Down, .. } => {
-      if let Mode::Users = greeter.mode {
-        if greeter.users.selected < greeter.users.options.len() - 1 {
-          greeter.users.selected += 1;
-        }
-      }
-
-      if let Mode::Sessions = greeter.mode {
-        if greeter.sessions.selected < greeter.sessions.options.len() - 1 {
-          greeter.sessions.selected += 1;
-        }
-      }
-
-      if let Mode::Power = greeter.mode {
-        if greeter.powers.selected < greeter.powers.options.len() - 1 {
-          greeter.powers.selected += 1;
-        }
-      }
-    }
+    KeyEvent { code: KeyCode::Down, .. } => navigate_menu(&mut greeter, KeyCode::Down),
 
     // ^A should go to the start of the current prompt
     KeyEvent {
@@ -185,46 +137,40 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
       ..
     } => greeter.cursor_offset = 0,
 
-    // Tab should validate the username entry (same as Enter).
+    // Text entries are only submitted with the configured modifiers held.
+    KeyEvent { code: KeyCode::Tab, modifiers, .. } if is_submit_ignored(&greeter, modifiers) => {}
+
+    // Tab should validate the username entry (same as Enter). There is only one
+    // answer field, so Tab does nothing on it unless configured to submit it.
     KeyEvent { code: KeyCode::Tab, .. } => match greeter.mode {
       Mode::Username if !greeter.username.value.is_empty() => validate_username(&mut greeter, &ipc).await,
+      Mode::Password if greeter.tab_submits_password => validate_answer(&mut greeter, &ipc).await,
+      Mode::Password => {}
       _ => {}
     },
 
+    // Text entries are only submitted with the configured modifiers held.
+    KeyEvent { code: KeyCode::Enter, modifiers, .. } if is_submit_ignored(&greeter, modifiers) => {}
+
     // Enter validates the current entry, depending on the active mode.
     KeyEvent { code: KeyCode::Enter, .. } => match greeter.mode {
       Mode::Username if !greeter.username.value.is_empty() => validate_username(&mut greeter, &ipc).await,
 
       Mode::Username if greeter.user_menu => {
-        greeter.previous_mode = match greeter.mode {
-          Mode::Users | Mode::Command | Mode::Sessions | Mode::Power => greeter.previous_mode,
-          _ => greeter.mode,
-        };
-
         greeter.buffer = greeter.previous_buffer.take().unwrap_or_default();
-        greeter.mode = Mode::Users;
+        greeter.enter_transient_mode(Mode::Users);
       }
 
       Mode::Username => {}
 
-      Mode::Password => {
-        greeter.working = true;
-        greeter.message = None;
-
-        ipc
-          .send(Request::PostAuthMessageResponse {
-            response: Some(greeter.buffer.clone()),
-          })
-          .await;
-
-        greeter.buffer = String::new();
-      }
+      Mode::Password => validate_answer(&mut greeter, &ipc).await,
 
       Mode::Command => {
         greeter.sessions.selected = 0;
         greeter.session_source = SessionSource::Command(greeter.buffer.clone());
+        greeter.session_chosen = true;
 
-        if greeter.remember_session {
+        if greeter.remember_session && !greeter.is_remember_excluded() {
           write_last_command(&greeter.buffer);
           delete_last_session();
         }
@@ -233,6 +179,15 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
         greeter.mode = greeter.previous_mode;
       }
 
+      Mode::Users if greeter.users.is_expand_selected() => {
+        if let Some(users) = greeter.all_users.take() {
+          greeter.users.options = users;
+        }
+
+        greeter.users.expand = None;
+        greeter.users.selected = 0;
+      }
+
       Mode::Users => {
         let username = greeter.users.options.get(greeter.users.selected).cloned();
 
@@ -246,18 +201,9 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
       }
 
       Mode::Sessions => {
-        let session = greeter.sessions.options.get(greeter.sessions.selected).cloned();
+        let index = greeter.sessions.selected;
 
-        if let Some(Session { path, .. }) = session {
-          if greeter.remember_session {
-            if let Some(ref path) = path {
-              write_last_session_path(path);
-              delete_last_command();
-            }
-          }
-
-          greeter.session_source = SessionSource::Session(greeter.sessions.selected);
-        }
+        select_session(&mut greeter, index);
 
         greeter.mode = greeter.previous_mode;
       }
@@ -278,6 +224,35 @@ pub async fn handle(greeter: Arc<RwLock<Greeter>>, input: KeyEvent, ipc: Ipc) ->
     // Do not handle any other controls keybindings
     KeyEvent { modifiers: KeyModifiers::CONTROL, .. } => {}
 
+    // With vi keys, j and k move down and up in menus, and g and G select the
+    // first and last options, instead of jumping to options.
+    KeyEvent { code: KeyCode::Char(c), .. } if greeter.vi_keys && matches!(c, 'j' | 'k' | 'g' | 'G') => {
+      let key = match c {
+        'j' => KeyCode::Down,
+        'k' => KeyCode::Up,
+        'g' => KeyCode::Home,
+        _ => KeyCode::End,
+      };
+
+      match greeter.mode {
+        Mode::Users | Mode::Sessions | Mode::Power => navigate_menu(&mut greeter, key),
+        _ => insert_key(&mut greeter, c).await,
+      }
+    }
+
+    // In menus, typing a letter jumps to the next option starting with it.
+    KeyEvent { code: KeyCode::Char(c), .. } if greeter.mode == Mode::Sessions => {
+      if let Some(index) = greeter.sessions.find_next(&greeter, c) {
+        greeter.sessions.selected = index;
+      }
+    }
+
+    KeyEvent { code: KeyCode::Char(c), .. } if greeter.mode == Mode::Users => {
+      if let Some(index) = greeter.users.find_next(&greeter, c) {
+        greeter.users.selected = index;
+      }
+    }
+
     // Handle free-form entry of characters.
     KeyEvent { code: KeyCode::Char(c), .. } => insert_key(&mut greeter, c).await,
 
@@ -304,7 +279,7 @@ async fn insert_key(greeter: &mut Greeter, c: char) {
   let left = value.chars().take(index);
   let right = value.chars().skip(index);
 
-  let value = left.chain(vec![c].into_iter()).chain(right).collect();
+  let value = left.chain(vec![c]).chain(right).collect();
   let mode = greeter.mode;
 
   match mode {
@@ -351,8 +326,186 @@ async fn delete_key(greeter: &mut Greeter, key: KeyCode) {
   }
 }
 
+// Sends the answer to the current prompt to `greetd`.
+async fn validate_answer(greeter: &mut Greeter, ipc: &Ipc) {
+  if is_session_choice_missing(greeter) {
+    return;
+  }
+
+  // A maintenance session selected by default, or before the username was
+  // typed, must still be refused to other users.
+  if let SessionSource::Session(index) = greeter.session_source {
+    if let Some(session) = greeter.sessions.options.get(index) {
+      if !greeter.is_session_allowed(session) {
+        greeter.set_error(fl!("maintenance_session_denied"));
+
+        return;
+      }
+    }
+  }
+
+  if greeter.asking_for_secret && !greeter.allow_empty_password && greeter.buffer.is_empty() {
+    greeter.set_error(fl!("empty_password"));
+
+    return;
+  }
+
+  greeter.working = true;
+  greeter.clear_message();
+
+  ipc
+    .send(Request::PostAuthMessageResponse {
+      response: Some(greeter.buffer.clone()),
+    })
+    .await;
+
+  if greeter.asking_for_secret && greeter.keep_password_on_failure {
+    greeter.previous_secret = Some(std::mem::take(&mut greeter.buffer));
+  } else {
+    greeter.buffer = String::new();
+  }
+}
+
+// Moves the selection in the active menu, if any, following a navigation key.
+fn navigate_menu(greeter: &mut Greeter, key: KeyCode) {
+  fn navigate<T: MenuItem>(menu: &mut Menu<T>, key: KeyCode) {
+    match key {
+      KeyCode::Up => menu.select_previous(),
+      KeyCode::Down => menu.select_next(),
+      KeyCode::Home => menu.select_first(),
+      KeyCode::End => menu.select_last(),
+      _ => {}
+    }
+  }
+
+  match greeter.mode {
+    Mode::Users => navigate(&mut greeter.users, key),
+    Mode::Sessions => navigate(&mut greeter.sessions, key),
+    Mode::Power => navigate(&mut greeter.powers, key),
+    _ => {}
+  }
+}
+
+// Whether pressing Enter with the provided modifiers should do nothing, because
+// the current entry can only be submitted with other modifiers held.
+fn is_submit_ignored(greeter: &Greeter, modifiers: KeyModifiers) -> bool {
+  let submitting = match greeter.mode {
+    Mode::Username => !greeter.username.value.is_empty(),
+    Mode::Password | Mode::Command => true,
+    _ => false,
+  };
+
+  submitting && !modifiers.contains(greeter.submit_modifiers)
+}
+
+// Makes the session at `index` the one that will be started, remembering it
+// if configured to.
+fn select_session(greeter: &mut Greeter, index: usize) {
+  let session = greeter.sessions.options.get(index).cloned();
+
+  if let Some(ref session) = session {
+    if !greeter.is_session_allowed(session) {
+      tracing::info!("user {} is not allowed to start the maintenance session", greeter.username.value);
+
+      greeter.set_error(fl!("maintenance_session_denied"));
+
+      return;
+    }
+  }
+
+  if let Some(Session { path, .. }) = session {
+    if greeter.remember_session && !greeter.is_remember_excluded() {
+      if let Some(ref path) = path {
+        write_last_session_path(path);
+        delete_last_command();
+      }
+    }
+
+    greeter.sessions.selected = index;
+    greeter.session_source = SessionSource::Session(index);
+    greeter.session_chosen = true;
+  }
+}
+
+// Selects the tab following the current session, skipping over the sessions
+// the user is not allowed to start.
+fn cycle_session_tabs(greeter: &mut Greeter) {
+  let count = greeter.sessions.options.len();
+  let current = match greeter.session_source {
+    SessionSource::Session(index) => index,
+    _ => count - 1,
+  };
+
+  let next = (1..=count)
+    .map(|offset| (current + offset) % count)
+    .find(|index| greeter.sessions.options.get(*index).map(|session| greeter.is_session_allowed(session)).unwrap_or(false));
+
+  if let Some(index) = next {
+    select_session(greeter, index);
+  }
+}
+
 // Creates a `greetd` session for the provided username.
 async fn validate_username(greeter: &mut Greeter, ipc: &Ipc) {
+  // The guest user gets its own command, so it does not need to pick a session.
+  let guest = greeter.is_guest() && greeter.guest_command.is_some();
+
+  if !guest && is_session_choice_missing(greeter) {
+    return;
+  }
+
+  // The validator runs in the background so the screen keeps being drawn while
+  // it works. Its verdict comes back as an `Event::UsernameValidated`, or is
+  // awaited right away if there is nowhere to send it.
+  if let Some(validator) = greeter.username_validator.clone() {
+    let username = greeter.username.value.clone();
+
+    match greeter.events {
+      Some(ref sender) => {
+        let sender = sender.clone();
+
+        greeter.working = true;
+
+        tokio::task::spawn(async move {
+          let valid = is_username_valid(&validator, &username).await;
+          let _ = sender.send(Event::UsernameValidated(username, valid)).await;
+        });
+      }
+
+      None => {
+        let valid = is_username_valid(&validator, &username).await;
+
+        username_validated(greeter, &username, valid, ipc).await;
+      }
+    }
+
+    return;
+  }
+
+  create_session(greeter, ipc).await;
+}
+
+// Resumes the login once the username validator has finished. The verdict is
+// ignored if the username was changed in the meantime.
+pub async fn username_validated(greeter: &mut Greeter, username: &str, valid: bool, ipc: &Ipc) {
+  greeter.working = false;
+
+  if greeter.mode != Mode::Username || greeter.username.value != username {
+    return;
+  }
+
+  if !valid {
+    tracing::info!("username {} was rejected by the validator", username);
+
+    greeter.set_error(fl!("unknown_user"));
+
+    return;
+  }
+
+  create_session(greeter, ipc).await;
+}
+
+async fn create_session(greeter: &mut Greeter, ipc: &Ipc) {
   greeter.working = true;
   greeter.message = None;
 
@@ -363,38 +516,130 @@ async fn validate_username(greeter: &mut Greeter, ipc: &Ipc) {
     .await;
   greeter.buffer = String::new();
 
-  if greeter.remember_user_session {
-    if let Ok(last_session) = get_last_user_session(&greeter.username.value) {
-      if let Some(last_session) = Session::from_path(greeter, last_session).cloned() {
-        tracing::info!("remembered user session is {}", last_session.name);
-
-        greeter.sessions.selected = greeter.sessions.options.iter().position(|sess| sess.path == last_session.path).unwrap_or(0);
-        greeter.session_source = SessionSource::Session(greeter.sessions.selected);
-      }
+  if greeter.show_last_login {
+    if let Some(time) = get_last_login(&greeter.username.value) {
+      greeter.set_message(fl!("last_login", time = time.format_localized("%c", greeter.locale).to_string()));
     }
+  }
 
-    if let Ok(command) = get_last_user_command(&greeter.username.value) {
-      tracing::info!("remembered user command is {}", command);
+  if greeter.remember_user_session && !greeter.is_guest() {
+    let command = get_last_user_command(&greeter.username.value).ok();
+    let session = get_last_user_session(&greeter.username.value).ok();
 
-      greeter.session_source = SessionSource::Command(command);
-    }
+    greeter.restore_user_session_source(command, session);
   }
 }
 
+// Runs the configured validator through the shell, with the username as its
+// last argument. A validator that cannot be run, or does not exit in time,
+// rejects the username.
+async fn is_username_valid(validator: &str, username: &str) -> bool {
+  let mut command = Command::new("sh");
+  command.arg("-c").arg(format!("{} \"$@\"", validator)).arg("sh").arg(username);
+  command.stdin(Stdio::null());
+  command.stdout(Stdio::null());
+  command.stderr(Stdio::null());
+  command.kill_on_drop(true);
+
+  match timeout(USERNAME_VALIDATOR_TIMEOUT, command.status()).await {
+    Ok(Ok(status)) => status.success(),
+    _ => false,
+  }
+}
+
+// Checks whether the user must still choose a session before logging in, and
+// asks them to do so if needed.
+fn is_session_choice_missing(greeter: &mut Greeter) -> bool {
+  if greeter.require_session_selection && !greeter.session_chosen {
+    greeter.set_error(fl!("select_session_first", key = format!("F{}", greeter.kb_sessions)));
+
+    return true;
+  }
+
+  false
+}
+
 #[cfg(test)]
 mod test {
-  use std::sync::Arc;
+  use std::{sync::Arc, time::Duration};
 
   use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-  use tokio::sync::RwLock;
+  use greetd_ipc::Request;
+  use tokio::{sync::RwLock, time::timeout};
 
-  use super::handle;
+  use super::{handle, is_username_valid};
   use crate::{
     ipc::Ipc,
-    ui::{common::masked::MaskedString, sessions::SessionSource},
+    ui::{
+      common::{masked::MaskedString, menu::Menu},
+      sessions::{Session, SessionSource},
+      users::User,
+    },
     Greeter, Mode,
   };
 
+  #[tokio::test]
+  async fn username_validator_quoting() {
+    assert!(is_username_valid("test 'apo gnu' =", "apo gnu").await);
+    assert!(!is_username_valid("test 'apo gnu' =", "apognu").await);
+    assert!(!is_username_valid("/nonexistent/validator", "apognu").await);
+  }
+
+  #[tokio::test]
+  async fn username_validator_without_events() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Username;
+      greeter.username = MaskedString::from("apognu".into(), None);
+      greeter.username_validator = Some("false".to_string());
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), Ipc::new()).await;
+
+    let status = greeter.read().await;
+
+    assert!(result.is_ok());
+    assert!(!status.working);
+    assert_eq!(status.mode, Mode::Username);
+    assert_eq!(status.message, Some(fl!("unknown_user")));
+  }
+
+  #[tokio::test]
+  async fn menu_first_letter_jump() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Sessions;
+      greeter.sessions = Menu {
+        title: "Sessions".into(),
+        options: ["GNOME", "Hyprland", "gamescope", "Sway"]
+          .iter()
+          .map(|name| Session {
+            name: name.to_string(),
+            ..Default::default()
+          })
+          .collect(),
+        selected: 0,
+        expand: None,
+      };
+    }
+
+    for expected in [2, 0, 2].iter() {
+      let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Char('g'), KeyModifiers::empty()), Ipc::new()).await;
+
+      assert_eq!(greeter.read().await.sessions.selected, *expected);
+    }
+
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Char('S'), KeyModifiers::SHIFT), Ipc::new()).await;
+    assert_eq!(greeter.read().await.sessions.selected, 3);
+
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Char('x'), KeyModifiers::empty()), Ipc::new()).await;
+    assert_eq!(greeter.read().await.sessions.selected, 3);
+  }
+
   #[tokio::test]
   async fn ctrl_u() {
     let greeter = Arc::new(RwLock::new(Greeter::default()));
@@ -410,7 +655,7 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.username.value, "".to_string());
     }
 
@@ -425,7 +670,7 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.buffer, "".to_string());
     }
 
@@ -440,7 +685,7 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.buffer, "".to_string());
     }
   }
@@ -463,10 +708,10 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.mode, Mode::Username);
       assert_eq!(status.buffer, "apognu".to_string());
-      assert!(matches!(status.previous_buffer, None));
+      assert!(status.previous_buffer.is_none());
       assert_eq!(status.cursor_offset, 0);
     }
 
@@ -482,7 +727,7 @@ mod test {
       {
         let status = greeter.read().await;
 
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
         assert_eq!(status.mode, Mode::Username);
       }
     }
@@ -497,7 +742,7 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.cursor_offset, -1);
     }
 
@@ -507,7 +752,7 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.cursor_offset, 1);
     }
   }
@@ -528,7 +773,7 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.mode, Mode::Command);
       assert_eq!(status.previous_buffer, Some("apognu".to_string()));
       assert_eq!(status.buffer, "thecommand".to_string());
@@ -546,7 +791,7 @@ mod test {
       {
         let status = greeter.read().await;
 
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
         assert_eq!(status.mode, Mode::Command);
         assert_eq!(status.previous_mode, Mode::Username);
       }
@@ -569,7 +814,7 @@ mod test {
       {
         let status = greeter.read().await;
 
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
         assert_eq!(status.mode, mode);
         assert_eq!(status.buffer, "apognu".to_string());
       }
@@ -586,7 +831,7 @@ mod test {
         {
           let status = greeter.read().await;
 
-          assert!(matches!(result, Ok(_)));
+          assert!(result.is_ok());
           assert_eq!(status.mode, Mode::Command);
           assert_eq!(status.previous_mode, Mode::Username);
         }
@@ -613,7 +858,7 @@ mod test {
       {
         let status = greeter.read().await;
 
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
         assert_eq!(status.mode, mode);
         assert_eq!(status.buffer, "apognu".to_string());
       }
@@ -630,7 +875,7 @@ mod test {
         {
           let status = greeter.read().await;
 
-          assert!(matches!(result, Ok(_)));
+          assert!(result.is_ok());
           assert_eq!(status.mode, Mode::Command);
           assert_eq!(status.previous_mode, Mode::Username);
         }
@@ -638,6 +883,127 @@ mod test {
     }
   }
 
+  #[tokio::test]
+  async fn no_double_submission() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+    let mut ipc = Ipc::new();
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Username;
+      greeter.username = MaskedString::from("apognu".to_string(), None);
+    }
+
+    for _ in 0..2 {
+      let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), ipc.clone()).await;
+
+      assert!(result.is_ok());
+    }
+
+    assert!(matches!(ipc.next().await, Some(Request::CreateSession { username }) if username == "apognu"));
+    assert!(timeout(Duration::from_millis(100), ipc.next()).await.is_err());
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.working = false;
+      greeter.mode = Mode::Password;
+      greeter.asking_for_secret = true;
+      greeter.buffer = "password".to_string();
+    }
+
+    for _ in 0..2 {
+      let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), ipc.clone()).await;
+
+      assert!(result.is_ok());
+    }
+
+    assert!(matches!(ipc.next().await, Some(Request::PostAuthMessageResponse { response: Some(response) }) if response == "password"));
+    assert!(timeout(Duration::from_millis(100), ipc.next()).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn empty_password_allowed() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+    let mut ipc = Ipc::new();
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Password;
+      greeter.asking_for_secret = true;
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), ipc.clone()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert!(status.working);
+      assert!(matches!(ipc.next().await, Some(Request::PostAuthMessageResponse { response: Some(response) }) if response.is_empty()));
+    }
+  }
+
+  #[tokio::test]
+  async fn empty_password_blocked() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+    let mut ipc = Ipc::new();
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Password;
+      greeter.asking_for_secret = true;
+      greeter.allow_empty_password = false;
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), ipc.clone()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert!(!status.working);
+      assert_eq!(status.mode, Mode::Password);
+      assert!(status.message.is_some());
+      assert!(timeout(Duration::from_millis(50), ipc.next()).await.is_err());
+    }
+  }
+
+  #[tokio::test]
+  async fn tab_password() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+    let mut ipc = Ipc::new();
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Password;
+      greeter.buffer = "password".to_string();
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Tab, KeyModifiers::empty()), ipc.clone()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert!(!status.working);
+      assert_eq!(status.buffer, "password".to_string());
+      assert!(timeout(Duration::from_millis(50), ipc.next()).await.is_err());
+    }
+
+    greeter.write().await.tab_submits_password = true;
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Tab, KeyModifiers::empty()), ipc.clone()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert!(status.working);
+      assert_eq!(status.buffer, "".to_string());
+      assert!(matches!(ipc.next().await, Some(Request::PostAuthMessageResponse { response: Some(response) }) if response == "password"));
+    }
+  }
+
   #[tokio::test]
   async fn ctrl_a_e() {
     let greeter = Arc::new(RwLock::new(Greeter::default()));
@@ -653,7 +1019,7 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.cursor_offset, -9);
     }
 
@@ -662,8 +1028,297 @@ mod test {
     {
       let status = greeter.read().await;
 
-      assert!(matches!(result, Ok(_)));
+      assert!(result.is_ok());
       assert_eq!(status.cursor_offset, 0);
     }
   }
+
+  #[tokio::test]
+  async fn vi_keys_in_menus() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Sessions;
+      greeter.vi_keys = true;
+      greeter.sessions = Menu::<Session> {
+        title: "Sessions".into(),
+        selected: 0,
+        options: ["Sway", "GNOME", "KDE"]
+          .iter()
+          .map(|name| Session {
+            name: name.to_string(),
+            ..Default::default()
+          })
+          .collect(),
+        expand: None,
+      };
+    }
+
+    for (key, expected) in [('j', 1), ('j', 2), ('j', 2), ('k', 1), ('g', 0), ('k', 0), ('G', 2)].iter() {
+      let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Char(*key), KeyModifiers::empty()), Ipc::new()).await;
+
+      assert!(result.is_ok());
+      assert_eq!(greeter.read().await.sessions.selected, *expected);
+    }
+
+    // Without vi keys, letters still jump to the options starting with them.
+    greeter.write().await.vi_keys = false;
+
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Char('g'), KeyModifiers::empty()), Ipc::new()).await;
+
+    assert_eq!(greeter.read().await.sessions.selected, 1);
+
+    // Outside of menus, they are typed as usual.
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Username;
+      greeter.vi_keys = true;
+    }
+
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Char('j'), KeyModifiers::empty()), Ipc::new()).await;
+
+    assert_eq!(greeter.read().await.username.value, "j");
+  }
+
+  #[tokio::test]
+  async fn submit_with_modifier() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.previous_mode = Mode::Username;
+      greeter.mode = Mode::Command;
+      greeter.buffer = "sway".to_string();
+      greeter.submit_modifiers = KeyModifiers::ALT;
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), Ipc::new()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert_eq!(status.mode, Mode::Command);
+      assert!(!status.session_chosen);
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT), Ipc::new()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert_eq!(status.mode, Mode::Username);
+      assert!(matches!(status.session_source, SessionSource::Command(ref command) if command == "sway"));
+      assert!(status.session_chosen);
+    }
+
+    // Tab does not submit either without the modifiers.
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Password;
+      greeter.tab_submits_password = true;
+      greeter.buffer = "hunter2".to_string();
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Tab, KeyModifiers::empty()), Ipc::new()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert!(!status.working);
+      assert_eq!(status.buffer, "hunter2");
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Tab, KeyModifiers::ALT), Ipc::new()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert!(status.working);
+    }
+  }
+
+  #[tokio::test]
+  async fn expand_recent_users() {
+    let user = |username: &str| User {
+      username: username.to_string(),
+      name: None,
+    };
+
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.previous_mode = Mode::Username;
+      greeter.mode = Mode::Users;
+      greeter.all_users = Some(vec![user("alice"), user("bob"), user("carol")]);
+      greeter.users = Menu::<User> {
+        title: "Users".into(),
+        selected: 0,
+        options: vec![user("bob"), user("dave")],
+        expand: Some("Show all users…".into()),
+      };
+    }
+
+    // The expanding entry can be reached with the arrows, but not by its first
+    // letter, and is not a user.
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Char('s'), KeyModifiers::empty()), Ipc::new()).await;
+    assert_eq!(greeter.read().await.users.selected, 0);
+
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Down, KeyModifiers::empty()), Ipc::new()).await;
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Down, KeyModifiers::empty()), Ipc::new()).await;
+    assert!(greeter.read().await.users.is_expand_selected());
+
+    let _ = handle(greeter.clone(), KeyEvent::new(KeyCode::Down, KeyModifiers::empty()), Ipc::new()).await;
+    assert_eq!(greeter.read().await.users.selected, 2);
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), Ipc::new()).await;
+
+    {
+      let mut status = greeter.write().await;
+
+      assert!(result.is_ok());
+      assert_eq!(status.mode, Mode::Users);
+      assert!(status.all_users.is_none());
+      assert!(status.users.expand.is_none());
+      assert_eq!(status.users.selected, 0);
+      assert!(status.users.options.iter().map(|user| user.username.as_str()).eq(["alice", "bob", "carol"]));
+
+      status.users.selected = 2;
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), Ipc::new()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert_eq!(status.username.value, "carol");
+    }
+
+    // Only the expanding entry is left, if no recent user is displayed.
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Users;
+      greeter.working = false;
+      greeter.all_users = Some(vec![user("alice")]);
+      greeter.users.options = vec![];
+      greeter.users.expand = Some("Show all users…".into());
+      greeter.users.selected = 0;
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), Ipc::new()).await;
+
+    assert!(result.is_ok());
+    assert!(greeter.read().await.users.options.iter().map(|user| user.username.as_str()).eq(["alice"]));
+  }
+
+  #[tokio::test]
+  async fn maintenance_session_users() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.previous_mode = Mode::Username;
+      greeter.mode = Mode::Sessions;
+      greeter.maintenance_session = Some("rescue".into());
+      greeter.maintenance_session_users = vec!["root".into()];
+      greeter.username = MaskedString::from("apognu".into(), None);
+      greeter.session_source = SessionSource::Session(0);
+      greeter.sessions = Menu::<Session> {
+        title: "Sessions".into(),
+        selected: 1,
+        options: vec![
+          Session {
+            slug: Some("sway".into()),
+            name: "Sway".into(),
+            ..Default::default()
+          },
+          Session {
+            slug: Some("rescue".into()),
+            name: "Rescue shell".into(),
+            ..Default::default()
+          },
+        ],
+        expand: None,
+      };
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), Ipc::new()).await;
+
+    {
+      let mut status = greeter.write().await;
+
+      assert!(result.is_ok());
+      assert_eq!(status.mode, Mode::Username);
+      assert!(matches!(status.session_source, SessionSource::Session(0)));
+      assert!(!status.session_chosen);
+      assert!(status.message.is_some());
+
+      status.mode = Mode::Sessions;
+      status.message = None;
+      status.username = MaskedString::from("root".into(), None);
+    }
+
+    let result = handle(greeter.clone(), KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()), Ipc::new()).await;
+
+    {
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert!(matches!(status.session_source, SessionSource::Session(1)));
+      assert!(status.message.is_none());
+    }
+  }
+
+  #[tokio::test]
+  async fn cycle_session_tabs() {
+    let greeter = Arc::new(RwLock::new(Greeter::default()));
+
+    {
+      let mut greeter = greeter.write().await;
+      greeter.mode = Mode::Username;
+      greeter.sessions_tabs = true;
+      greeter.maintenance_session = Some("rescue".into());
+      greeter.session_source = SessionSource::Session(0);
+      greeter.sessions = Menu::<Session> {
+        title: "Sessions".into(),
+        selected: 0,
+        options: vec![
+          Session {
+            slug: Some("sway".into()),
+            name: "Sway".into(),
+            ..Default::default()
+          },
+          Session {
+            slug: Some("rescue".into()),
+            name: "Rescue shell".into(),
+            ..Default::default()
+          },
+          Session {
+            slug: Some("gnome".into()),
+            name: "GNOME".into(),
+            ..Default::default()
+          },
+        ],
+        expand: None,
+      };
+    }
+
+    for expected in [2, 0].iter() {
+      let result = handle(greeter.clone(), KeyEvent::new(KeyCode::F(3), KeyModifiers::empty()), Ipc::new()).await;
+
+      let status = greeter.read().await;
+
+      assert!(result.is_ok());
+      assert_eq!(status.mode, Mode::Username);
+      assert!(matches!(status.session_source, SessionSource::Session(index) if index == *expected));
+      assert_eq!(status.sessions.selected, *expected);
+      assert!(status.session_chosen);
+    }
+  }
 }
@@ -15,16 +15,29 @@ mod ui;
 #[cfg(test)]
 mod integration;
 
-use std::{error::Error, fs::OpenOptions, io, process, sync::Arc};
+use std::{
+  error::Error,
+  fs::OpenOptions,
+  io,
+  os::fd::{AsFd, AsRawFd},
+  process,
+  sync::Arc,
+  time::Duration,
+};
 
 use crossterm::{
+  cursor::SetCursorStyle,
   execute,
   terminal::{disable_raw_mode, LeaveAlternateScreen},
 };
 use event::Event;
 use greetd_ipc::Request;
+use nix::{
+  errno::Errno,
+  sys::inotify::{AddWatchFlags, InitFlags, Inotify},
+};
 use power::PowerPostAction;
-use tokio::sync::RwLock;
+use tokio::{io::unix::AsyncFd, sync::RwLock};
 use tracing_appender::non_blocking::WorkerGuard;
 use tui::{backend::CrosstermBackend, Terminal};
 
@@ -32,7 +45,11 @@ use tui::{backend::CrosstermBackend, Terminal};
 use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
 
 pub use self::greeter::*;
-use self::{event::Events, ipc::Ipc};
+use self::{event::Events, info::get_session_paths, ipc::Ipc};
+
+// Time to wait for changes to session files to settle before reloading them,
+// since they are usually written in several steps.
+const SESSIONS_WATCH_DELAY: Duration = Duration::from_millis(200);
 
 #[tokio::main]
 async fn main() {
@@ -55,7 +72,7 @@ where
 {
   tracing::info!("tuigreet started");
 
-  register_panic_handler();
+  register_panic_handler(greeter.steady_cursor);
 
   #[cfg(not(test))]
   {
@@ -70,7 +87,7 @@ where
 
   let ipc = Ipc::new();
 
-  if greeter.remember && !greeter.username.value.is_empty() {
+  if greeter.remember && greeter.auto_create_session && !greeter.username.value.is_empty() {
     greeter.working = true;
 
     tracing::info!("creating remembered session for user {}", greeter.username.value);
@@ -95,6 +112,18 @@ where
     }
   });
 
+  if greeter.read().await.watch_sessions {
+    tokio::task::spawn({
+      let greeter = greeter.clone();
+
+      async move {
+        if let Err(err) = watch_sessions(greeter).await {
+          tracing::error!("could not watch session files: {}", err);
+        }
+      }
+    });
+  }
+
   loop {
     if let Some(status) = greeter.read().await.exit {
       tracing::info!("exiting main loop");
@@ -105,6 +134,7 @@ where
     match events.next().await {
       Some(Event::Render) => ui::draw(greeter.clone(), &mut terminal).await?,
       Some(Event::Key(key)) => keyboard::handle(greeter.clone(), key, ipc.clone()).await?,
+      Some(Event::UsernameValidated(username, valid)) => keyboard::username_validated(&mut *greeter.write().await, &username, valid, &ipc).await,
 
       Some(Event::Exit(status)) => {
         crate::exit(&mut *greeter.write().await, status).await;
@@ -112,6 +142,8 @@ where
 
       Some(Event::PowerCommand(command)) => {
         if let PowerPostAction::ClearScreen = power::run(&greeter, command).await {
+          restore_cursor_style(greeter.read().await.steady_cursor);
+
           execute!(io::stdout(), LeaveAlternateScreen)?;
           terminal.set_cursor(1, 1)?;
           terminal.clear()?;
@@ -128,6 +160,50 @@ where
   Ok(())
 }
 
+// Reloads the sessions every time a file is created, changed or removed in one
+// of the session directories. Directories that do not exist are not watched.
+async fn watch_sessions(greeter: Arc<RwLock<Greeter>>) -> Result<(), Box<dyn Error>> {
+  let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+  let flags = AddWatchFlags::IN_CREATE | AddWatchFlags::IN_DELETE | AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_FROM | AddWatchFlags::IN_MOVED_TO;
+
+  for (path, _) in get_session_paths(&*greeter.read().await) {
+    match inotify.add_watch(&path, flags) {
+      Ok(_) => tracing::info!("watching sessions in '{}'", path.display()),
+      Err(err) => tracing::info!("not watching sessions in '{}': {}", path.display(), err),
+    }
+  }
+
+  let fd = AsyncFd::new(inotify.as_fd().as_raw_fd())?;
+
+  loop {
+    let mut guard = fd.readable().await?;
+
+    tokio::time::sleep(SESSIONS_WATCH_DELAY).await;
+
+    // Drain all pending events, a single reload covers all of them.
+    loop {
+      match inotify.read_events() {
+        Ok(_) => {}
+        Err(Errno::EAGAIN) => break,
+        Err(err) => return Err(err.into()),
+      }
+    }
+
+    guard.clear_ready();
+
+    let events = {
+      let mut greeter = greeter.write().await;
+
+      greeter.refresh_sessions();
+      greeter.events.clone()
+    };
+
+    if let Some(sender) = events {
+      let _ = sender.send(Event::Render).await;
+    }
+  }
+}
+
 async fn exit(greeter: &mut Greeter, status: AuthStatus) {
   tracing::info!("preparing exit with status {}", status);
 
@@ -139,19 +215,23 @@ async fn exit(greeter: &mut Greeter, status: AuthStatus) {
   #[cfg(not(test))]
   clear_screen();
 
+  restore_cursor_style(greeter.steady_cursor);
+
   let _ = execute!(io::stdout(), LeaveAlternateScreen);
   let _ = disable_raw_mode();
 
   greeter.exit = Some(status);
 }
 
-fn register_panic_handler() {
+fn register_panic_handler(steady_cursor: bool) {
   let hook = std::panic::take_hook();
 
   std::panic::set_hook(Box::new(move |info| {
     #[cfg(not(test))]
     clear_screen();
 
+    restore_cursor_style(steady_cursor);
+
     let _ = execute!(io::stdout(), LeaveAlternateScreen);
     let _ = disable_raw_mode();
 
@@ -159,6 +239,13 @@ fn register_panic_handler() {
   }));
 }
 
+// Gives the cursor back its default style, if we changed it.
+fn restore_cursor_style(steady_cursor: bool) {
+  if steady_cursor {
+    let _ = execute!(io::stdout(), SetCursorStyle::DefaultUserShape);
+  }
+}
+
 #[cfg(not(test))]
 pub fn clear_screen() {
   let backend = CrosstermBackend::new(io::stdout());
@@ -191,3 +278,70 @@ fn init_logger(greeter: &Greeter) -> Option<WorkerGuard> {
     _ => None,
   }
 }
+
+#[cfg(test)]
+mod test {
+  use std::{fs, sync::Arc, time::Duration};
+
+  use tokio::{sync::RwLock, time::timeout};
+
+  use crate::{
+    ui::sessions::{SessionSource, SessionType},
+    Greeter,
+  };
+
+  use super::watch_sessions;
+
+  #[tokio::test]
+  async fn watch_session_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let sway = dir.path().join("sway.desktop");
+    let cage = dir.path().join("cage.desktop");
+
+    fs::write(&sway, "[Desktop Entry]\nName=Sway\nExec=sway\n").unwrap();
+
+    let mut greeter = Greeter::default();
+    greeter.session_paths = vec![(dir.path().to_path_buf(), SessionType::Wayland)];
+    greeter.refresh_sessions();
+
+    let index = greeter.sessions.options.iter().position(|session| session.path.as_ref() == Some(&sway)).unwrap();
+
+    greeter.sessions.selected = index;
+    greeter.session_source = SessionSource::Session(index);
+
+    let greeter = Arc::new(RwLock::new(greeter));
+    let watcher = tokio::task::spawn({
+      let greeter = greeter.clone();
+
+      async move {
+        let _ = watch_sessions(greeter).await;
+      }
+    });
+
+    // Leave time for the watches to be set up before changing any file.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    fs::write(&cage, "[Desktop Entry]\nName=Cage\nExec=cage\n").unwrap();
+
+    let reloaded = timeout(Duration::from_secs(2), async {
+      loop {
+        if greeter.read().await.sessions.options.iter().any(|session| session.path.as_ref() == Some(&cage)) {
+          break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+      }
+    })
+    .await;
+
+    watcher.abort();
+
+    assert!(reloaded.is_ok(), "sessions were not reloaded");
+
+    let greeter = greeter.read().await;
+    let sway_index = greeter.sessions.options.iter().position(|session| session.path.as_ref() == Some(&sway)).unwrap();
+
+    assert_eq!(greeter.sessions.selected, sway_index);
+    assert!(matches!(greeter.session_source, SessionSource::Session(index) if index == sway_index));
+  }
+}
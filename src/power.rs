@@ -11,6 +11,14 @@ pub enum PowerOption {
   Reboot,
 }
 
+// Command run for a power option when none was configured.
+pub fn default_command(option: PowerOption) -> &'static str {
+  match option {
+    PowerOption::Shutdown => "shutdown -h now",
+    PowerOption::Reboot => "shutdown -r now",
+  }
+}
+
 pub async fn power(greeter: &mut Greeter, option: PowerOption) {
   let command = match greeter.powers.options.iter().find(|opt| opt.action == option) {
     None => None,
@@ -36,14 +44,10 @@ pub async fn power(greeter: &mut Greeter, option: PowerOption) {
     }
 
     Some(_) => {
-      let mut command = Command::new("shutdown");
+      let mut args = default_command(option).split(' ');
 
-      match option {
-        PowerOption::Shutdown => command.arg("-h"),
-        PowerOption::Reboot => command.arg("-r"),
-      };
-
-      command.arg("now");
+      let mut command = Command::new(args.next().unwrap_or_default());
+      command.args(args);
 
       Some(command)
     }
@@ -86,16 +90,62 @@ pub async fn run(greeter: &Arc<RwLock<Greeter>>, mut command: Command) -> PowerP
 
   tracing::info!("power command exited with: {:?}", message);
 
-  let mode = greeter.read().await.previous_mode;
-
   let mut greeter = greeter.write().await;
 
-  if message.is_none() {
-    PowerPostAction::ClearScreen
-  } else {
-    greeter.mode = mode;
-    greeter.message = message;
+  match message {
+    None => PowerPostAction::ClearScreen,
+
+    Some(message) => {
+      // The previous mode is left untouched when staying in the power menu, so
+      // that closing it still goes back to where the user was.
+      greeter.mode = match greeter.power_stay_on_failure {
+        true => Mode::Power,
+        false => greeter.previous_mode,
+      };
+      greeter.set_error(message);
+
+      PowerPostAction::Noop
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use tokio::{process::Command, sync::RwLock};
+
+  use super::{run, PowerPostAction};
+  use crate::{Greeter, Mode};
+
+  async fn run_failing_command(stay_on_failure: bool) -> (PowerPostAction, Greeter) {
+    let mut greeter = Greeter::default();
+    greeter.mode = Mode::Power;
+    greeter.previous_mode = Mode::Username;
+    greeter.power_stay_on_failure = stay_on_failure;
+
+    let greeter = Arc::new(RwLock::new(greeter));
+    let action = run(&greeter, Command::new("false")).await;
+
+    (action, Arc::try_unwrap(greeter).ok().unwrap().into_inner())
+  }
+
+  #[tokio::test]
+  async fn failure_restores_previous_mode() {
+    let (action, greeter) = run_failing_command(false).await;
+
+    assert!(matches!(action, PowerPostAction::Noop));
+    assert_eq!(greeter.mode, Mode::Username);
+    assert!(greeter.message.is_some());
+  }
+
+  #[tokio::test]
+  async fn failure_stays_in_power_menu() {
+    let (action, greeter) = run_failing_command(true).await;
 
-    PowerPostAction::Noop
+    assert!(matches!(action, PowerPostAction::Noop));
+    assert_eq!(greeter.mode, Mode::Power);
+    assert_eq!(greeter.previous_mode, Mode::Username);
+    assert!(greeter.message.is_some());
   }
 }
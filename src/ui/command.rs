@@ -44,22 +44,16 @@ pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn
 
   let command_label_text = prompt_value(theme, Some(fl!("new_command")));
   let command_label = Paragraph::new(command_label_text).style(theme.of(&[Themed::Prompt]));
-  let command_value_text = Span::from(&greeter.buffer);
+  let command_width = get_input_width(greeter, width, &Some(fl!("new_command")));
+  let command_scroll = get_input_scroll(greeter, greeter.buffer.chars().count(), command_width);
+  let command_value_text = Span::from(get_visible_input(&greeter.buffer, command_scroll));
   let command_value = Paragraph::new(command_value_text).style(theme.of(&[Themed::Input]));
 
   f.render_widget(command_label, chunks[0]);
-  f.render_widget(
-    command_value,
-    Rect::new(
-      1 + chunks[0].x + fl!("new_command").chars().count() as u16,
-      chunks[0].y,
-      get_input_width(greeter, width, &Some(fl!("new_command"))),
-      1,
-    ),
-  );
+  f.render_widget(command_value, Rect::new(1 + chunks[0].x + fl!("new_command").chars().count() as u16, chunks[0].y, command_width, 1));
 
   let new_command = greeter.buffer.clone();
   let offset = get_cursor_offset(greeter, new_command.chars().count());
 
-  Ok((2 + cursor.x + fl!("new_command").chars().count() as u16 + offset as u16, cursor.y + 1))
+  Ok((2 + cursor.x + fl!("new_command").chars().count() as u16 + (offset as usize - command_scroll) as u16, cursor.y + 1))
 }
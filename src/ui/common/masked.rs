@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use zeroize::Zeroize;
 
 #[derive(Default)]
@@ -18,6 +20,14 @@ impl MaskedString {
     }
   }
 
+  // Returns the mask followed by the actual value, if masked.
+  pub fn get_with_value(&self) -> Cow<'_, str> {
+    match self.mask {
+      Some(ref mask) => Cow::Owned(format!("{mask} ({})", self.value)),
+      None => Cow::Borrowed(&self.value),
+    }
+  }
+
   pub fn zeroize(&mut self) {
     self.value.zeroize();
 
@@ -46,4 +56,15 @@ mod tests {
 
     assert_eq!(masked.get(), "mask");
   }
+
+  #[test]
+  fn get_mask_and_value() {
+    let masked = MaskedString::from("value".to_string(), Some("mask".to_string()));
+
+    assert_eq!(masked.get_with_value(), "mask (value)");
+
+    let masked = MaskedString::from("value".to_string(), None);
+
+    assert_eq!(masked.get_with_value(), "value");
+  }
 }
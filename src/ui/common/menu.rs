@@ -1,24 +1,30 @@
 use std::{borrow::Cow, error::Error};
 
 use tui::{
+  layout::Alignment,
   prelude::Rect,
   style::{Modifier, Style},
-  text::Span,
+  text::{Line, Span},
   widgets::{Block, BorderType, Borders, Paragraph},
 };
 
 use crate::{
   ui::{
-    util::{get_rect_bounds, titleize},
+    util::{get_message_height, get_rect_bounds, titleize},
     Frame,
   },
-  Greeter,
+  Greeter, Mode,
 };
 
 use super::style::Themed;
 
 pub trait MenuItem {
-  fn format(&self) -> Cow<'_, str>;
+  fn format(&self, greeter: &Greeter) -> Cow<'_, str>;
+
+  // Additional information displayed after the item, if requested.
+  fn hint(&self, _greeter: &Greeter) -> Option<Cow<'_, str>> {
+    None
+  }
 }
 
 #[derive(Default)]
@@ -29,17 +35,27 @@ where
   pub title: String,
   pub options: Vec<T>,
   pub selected: usize,
+  // Label of an extra entry displayed after the options, used to expand the
+  // menu when it is selected.
+  pub expand: Option<String>,
 }
 
 impl<T> Menu<T>
 where
   T: MenuItem,
 {
-  pub fn draw(&self, greeter: &Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn Error>> {
+  // Draws the menu, displaying at most `limit` options at once. When some
+  // options do not fit, the displayed window follows the selected option and
+  // the number of hidden options is shown below.
+  pub fn draw(&self, greeter: &Greeter, f: &mut Frame, limit: Option<usize>) -> Result<(u16, u16), Box<dyn Error>> {
     let theme = &greeter.theme;
 
+    let (start, count) = self.get_window(limit);
+    let hidden = self.len() - count;
+    let items = if hidden > 0 { count + 1 } else { count };
+
     let size = f.size();
-    let (x, y, width, height) = get_rect_bounds(greeter, size, self.options.len());
+    let (x, y, width, height) = get_rect_bounds(greeter, size, items);
 
     let container = Rect::new(x, y, width, height);
 
@@ -52,30 +68,110 @@ where
       .border_type(BorderType::Plain)
       .border_style(theme.of(&[Themed::Border]));
 
-    for (index, option) in self.options.iter().enumerate() {
-      let name = option.format();
-      let name = format!("{:1$}", name, greeter.width() as usize - 4);
+    for (row, index) in (start..start + count).enumerate() {
+      let (name, hint) = match self.options.get(index) {
+        Some(option) => (option.format(greeter), option.hint(greeter).map(|hint| format!(" ({hint})"))),
+        None => (Cow::Borrowed(self.expand.as_deref().unwrap_or_default()), None),
+      };
 
-      let frame = Rect::new(x + 2, y + 2 + index as u16, width - 4, 1);
-      let option_text = self.get_option(name, index);
+      let padding = (greeter.width() as usize - 4).saturating_sub(hint.as_ref().map(|hint| hint.chars().count()).unwrap_or_default());
+      let name = format!("{:1$}", name, padding);
+
+      let frame = Rect::new(x + 2, y + 2 + row as u16, width - 4, 1);
+      let option_text = self.get_option(name, hint, index);
       let option = Paragraph::new(option_text);
 
       f.render_widget(option, frame);
     }
 
+    if hidden > 0 {
+      let frame = Rect::new(x + 2, y + 2 + count as u16, width - 4, 1);
+      let more = Paragraph::new(Span::from(fl!("menu_more", count = hidden)));
+
+      f.render_widget(more, frame);
+    }
+
     f.render_widget(block, container);
 
+    // The error of a failed power command is displayed below the power menu
+    // when it is kept open.
+    if greeter.mode == Mode::Power {
+      if let (Some(message), message_height) = get_message_height(greeter, 1, 0) {
+        f.render_widget(message.alignment(Alignment::Center), Rect::new(x, y + height, width, message_height));
+      }
+    }
+
     Ok((1, 1))
   }
 
-  fn get_option<'g, S>(&self, name: S, index: usize) -> Span<'g>
+  pub fn select_previous(&mut self) {
+    self.selected = self.selected.saturating_sub(1);
+  }
+
+  pub fn select_next(&mut self) {
+    if self.selected + 1 < self.len() {
+      self.selected += 1;
+    }
+  }
+
+  pub fn select_first(&mut self) {
+    self.selected = 0;
+  }
+
+  pub fn select_last(&mut self) {
+    self.selected = self.len().saturating_sub(1);
+  }
+
+  // Whether the entry expanding the menu is the selected one.
+  pub fn is_expand_selected(&self) -> bool {
+    self.expand.is_some() && self.selected == self.options.len()
+  }
+
+  // Number of displayed entries, including the one expanding the menu.
+  fn len(&self) -> usize {
+    self.options.len() + self.expand.iter().count()
+  }
+
+  // Finds the next option starting with the provided letter, wrapping around
+  // to the first options if none is found after the current one.
+  pub fn find_next(&self, greeter: &Greeter, letter: char) -> Option<usize> {
+    let len = self.options.len();
+    let letter = letter.to_lowercase().collect::<String>();
+
+    (1..=len)
+      .map(|offset| (self.selected + offset) % len)
+      .find(|index| self.options[*index].format(greeter).to_lowercase().starts_with(&letter))
+  }
+
+  // Computes the index of the first displayed option, and how many options
+  // should be displayed, so that the selected option is always visible.
+  fn get_window(&self, limit: Option<usize>) -> (usize, usize) {
+    match limit {
+      Some(limit) if limit < self.len() => {
+        let start = if self.selected < limit { 0 } else { self.selected + 1 - limit };
+
+        (start, limit)
+      }
+
+      _ => (0, self.len()),
+    }
+  }
+
+  fn get_option<'g, S>(&self, name: S, hint: Option<String>, index: usize) -> Line<'g>
   where
     S: Into<String>,
   {
-    if self.selected == index {
-      Span::styled(name.into(), Style::default().add_modifier(Modifier::REVERSED))
-    } else {
-      Span::from(name.into())
+    let style = match self.selected == index {
+      true => Style::default().add_modifier(Modifier::REVERSED),
+      false => Style::default(),
+    };
+
+    let mut line = Line::from(Span::styled(name.into(), style));
+
+    if let Some(hint) = hint {
+      line.spans.push(Span::styled(hint, style.add_modifier(Modifier::DIM)));
     }
+
+    line
   }
 }
@@ -2,6 +2,10 @@ use std::str::FromStr;
 
 use tui::style::{Color, Style};
 
+// Caps lock during password entry is a common cause of failures, so its
+// indicator stands out even without a theme.
+const DEFAULT_CAPS_WARNING: Option<(Component, Color)> = Some((Component::Fg, Color::Red));
+
 #[derive(Clone)]
 enum Component {
   Bg,
@@ -19,6 +23,8 @@ pub enum Themed {
   Input,
   Action,
   ActionButton,
+  Separator,
+  CapsWarning,
 }
 
 #[derive(Default)]
@@ -33,6 +39,8 @@ pub struct Theme {
   input: Option<(Component, Color)>,
   action: Option<(Component, Color)>,
   button: Option<(Component, Color)>,
+  separator: Option<(Component, Color)>,
+  caps_warning: Option<(Component, Color)>,
 }
 
 impl Theme {
@@ -55,6 +63,8 @@ impl Theme {
           "input" => style.input = Some((Fg, color)),
           "action" => style.action = Some((Fg, color)),
           "button" => style.button = Some((Fg, color)),
+          "separator" => style.separator = Some((Fg, color)),
+          "capswarning" => style.caps_warning = Some((Fg, color)),
           _ => {}
         }
       }
@@ -72,6 +82,9 @@ impl Theme {
     if style.button.is_none() {
       style.button.clone_from(&style.action);
     }
+    if style.separator.is_none() {
+      style.separator.clone_from(&style.action);
+    }
 
     style
   }
@@ -94,6 +107,11 @@ impl Theme {
       Input => &self.input,
       Action => &self.action,
       ActionButton => &self.button,
+      Separator => &self.separator,
+      CapsWarning => match self.caps_warning {
+        Some(_) => &self.caps_warning,
+        None => &DEFAULT_CAPS_WARNING,
+      },
     };
 
     match color {
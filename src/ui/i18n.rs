@@ -20,3 +20,39 @@ lazy_static! {
     loader
   };
 }
+
+// Loads the messages of a single language, falling back to English, without
+// looking at the environment.
+#[cfg(test)]
+pub fn messages_for(language: &str) -> FluentLanguageLoader {
+  let loader = fluent_language_loader!();
+  let language: unic_langid::LanguageIdentifier = language.parse().unwrap();
+
+  loader.load_languages(&Localizations, &[&language, loader.fallback_language()]).unwrap();
+
+  loader
+}
+
+// Lists the locales bundled with the binary, as found in `contrib/locales`.
+pub fn bundled_locales() -> Vec<String> {
+  let mut locales = Localizations::iter().filter_map(|file| file.split('/').next().map(str::to_string)).collect::<Vec<_>>();
+
+  locales.sort();
+  locales.dedup();
+
+  locales
+}
+
+#[cfg(test)]
+mod test {
+  use super::bundled_locales;
+
+  #[test]
+  fn list_bundled_locales() {
+    let locales = bundled_locales();
+
+    assert!(locales.contains(&"en-US".to_string()));
+    assert!(locales.contains(&"fr-FR".to_string()));
+    assert_eq!(locales.iter().filter(|locale| *locale == "en-US").count(), 1);
+  }
+}
@@ -13,27 +13,39 @@ use std::{
   error::Error,
   io::{self, Write},
   sync::Arc,
+  time::{Duration, Instant},
 };
 
 use chrono::prelude::*;
+use crossterm::execute;
 use sessions::SessionSource;
 use tokio::sync::RwLock;
 use tui::{
-  layout::{Alignment, Constraint, Direction, Layout},
-  style::Modifier,
+  layout::{Alignment, Constraint, Direction, Layout, Rect},
+  style::{Modifier, Style},
   text::{Line, Span},
-  widgets::Paragraph,
+  widgets::{Paragraph, Tabs},
   Frame as CrosstermFrame, Terminal,
 };
-use util::buttonize;
+use util::{buttonize, truncate};
 
-use crate::{info::capslock_status, ui::util::should_hide_cursor, Greeter, Mode};
+use crate::{
+  info::{capslock_status, format_size, get_free_space, get_hostname, get_network_status},
+  ui::util::{get_cursor_style, should_hide_cursor},
+  GreetAlign, Greeter, GreetingRotation, Mode,
+};
 
 use self::common::style::{Theme, Themed};
-pub use self::i18n::MESSAGES;
+pub use self::i18n::{bundled_locales, MESSAGES};
+
+#[cfg(test)]
+pub use self::i18n::messages_for;
 
 const TITLEBAR_INDEX: usize = 1;
-const STATUSBAR_INDEX: usize = 3;
+const TABS_INDEX: usize = 2;
+
+const GREETING_ROTATION_INTERVAL: Duration = Duration::from_secs(60);
+const STATUSBAR_INDEX: usize = 4;
 const STATUSBAR_LEFT_INDEX: usize = 1;
 const STATUSBAR_RIGHT_INDEX: usize = 2;
 
@@ -51,17 +63,42 @@ where
   B: tui::backend::Backend,
 {
   let mut greeter = greeter.write().await;
+
+  greeter.expire_message(Instant::now());
+
   let hide_cursor = should_hide_cursor(&greeter);
 
+  if greeter.greeting_rotate == GreetingRotation::Minute {
+    match greeter.greeting_time {
+      Some(time) if time.elapsed() >= GREETING_ROTATION_INTERVAL => greeter.rotate_greeting(),
+      Some(_) => {}
+      None => greeter.greeting_time = Some(Instant::now()),
+    }
+  }
+
+  let diskfree = match greeter.diskfree_mountpoint.clone() {
+    Some(mountpoint) => greeter.diskfree.get(Instant::now(), || get_free_space(&mountpoint)).map(|free| (mountpoint, free)),
+    None => None,
+  };
+
+  let network = match greeter.show_network {
+    true => Some(greeter.network.get(Instant::now(), get_network_status)),
+    false => None,
+  };
+
+  let transitioning = is_transitioning(&mut greeter);
+  let session_tabs_height = if greeter.show_session_tabs() { 1 } else { 0 };
+
   terminal.draw(|f| {
     let theme = &greeter.theme;
 
-    let size = f.size();
+    let size = get_content_area(&greeter, f.size());
     let chunks = Layout::default()
       .constraints(
         [
           Constraint::Length(greeter.window_padding()), // Top vertical padding
           Constraint::Length(1),                        // Date and time
+          Constraint::Length(session_tabs_height),      // Session tabs
           Constraint::Min(1),                           // Main area
           Constraint::Length(1),                        // Status line
           Constraint::Length(greeter.window_padding()), // Bottom vertical padding
@@ -71,10 +108,25 @@ where
       .split(size);
 
     if greeter.time {
+      let time_alignment = match greeter.time_align() {
+        GreetAlign::Center => Alignment::Center,
+        GreetAlign::Left => Alignment::Left,
+        GreetAlign::Right => Alignment::Right,
+      };
+
+      let time_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(greeter.window_padding()), Constraint::Min(1), Constraint::Length(greeter.window_padding())].as_ref())
+        .split(chunks[TITLEBAR_INDEX]);
+
       let time_text = Span::from(get_time(&greeter));
-      let time = Paragraph::new(time_text).alignment(Alignment::Center).style(theme.of(&[Themed::Time]));
+      let time = Paragraph::new(time_text).alignment(time_alignment).style(theme.of(&[Themed::Time]));
 
-      f.render_widget(time, chunks[TITLEBAR_INDEX]);
+      f.render_widget(time, time_chunks[1]);
+    }
+
+    if greeter.show_session_tabs() {
+      draw_session_tabs(&greeter, f, chunks[TABS_INDEX]);
     }
 
     let status_block_size_right = 1 + greeter.window_padding() + fl!("status_caps").chars().count() as u16;
@@ -100,28 +152,70 @@ where
 
     let session_source = greeter.session_source.label(&greeter).unwrap_or("-");
 
-    let status_left_text = Line::from(vec![
+    let mut status_left_text = Line::default();
+
+    if let Some(footer) = get_footer(&greeter) {
+      status_left_text.spans.push(Span::from(footer));
+      status_left_text.spans.push(status_separator(&greeter, theme));
+    }
+
+    status_left_text.spans.extend([
       status_label(theme, "ESC"),
       status_value(&greeter, theme, Button::Other, fl!("action_reset")),
-      Span::from(" "),
+      status_separator(&greeter, theme),
       status_label(theme, format!("F{}", greeter.kb_command)),
       status_value(&greeter, theme, Button::Command, fl!("action_command")),
-      Span::from(" "),
+      status_separator(&greeter, theme),
       status_label(theme, format!("F{}", greeter.kb_sessions)),
       status_value(&greeter, theme, Button::Session, fl!("action_session")),
-      Span::from(" "),
+      status_separator(&greeter, theme),
       status_label(theme, format!("F{}", greeter.kb_power)),
       status_value(&greeter, theme, Button::Power, fl!("action_power")),
-      Span::from(" "),
+      status_separator(&greeter, theme),
       status_label(theme, session_source_label),
-      status_value(&greeter, theme, Button::Other, session_source),
     ]);
+
+    let mut badges: Vec<Span> = Vec::new();
+
+    if greeter.show_session_count {
+      badges.push(status_separator(&greeter, theme));
+      badges.push(status_label(theme, fl!("status_session_count", count = greeter.sessions.options.len())));
+    }
+
+    if let Some((ref mountpoint, free)) = diskfree {
+      badges.push(status_separator(&greeter, theme));
+      badges.push(status_label(theme, fl!("status_diskfree", mountpoint = mountpoint.display().to_string(), size = format_size(free))));
+    }
+
+    if let Some(online) = network {
+      badges.push(status_separator(&greeter, theme));
+      badges.push(status_label(theme, if online { fl!("status_online") } else { fl!("status_offline") }));
+    }
+
+    if let Some(count) = greeter.active_sessions {
+      if count > 0 {
+        badges.push(status_separator(&greeter, theme));
+        badges.push(status_label(theme, fl!("status_active_sessions", count = count)));
+      }
+    }
+
+    // Free-form commands can be arbitrarily long, so only keep what fits in the
+    // status bar next to the badges following it, accounting for the space
+    // added before the value.
+    let badges_width: usize = badges.iter().map(Span::width).sum();
+    let session_source_width = (status_block_size_left as usize).saturating_sub(status_left_text.width() + badges_width + 1);
+
+    status_left_text
+      .spans
+      .push(status_value(&greeter, theme, Button::Other, truncate(session_source, session_source_width)));
+    status_left_text.spans.extend(badges);
+
     let status_left = Paragraph::new(status_left_text);
 
     f.render_widget(status_left, status_chunks[STATUSBAR_LEFT_INDEX]);
 
-    if capslock_status() {
-      let status_right_text = status_label(theme, fl!("status_caps"));
+    if should_show_capslock(&greeter, capslock_status) {
+      let status_right_text = capslock_label(&greeter, theme);
       let status_right = Paragraph::new(status_right_text).alignment(Alignment::Right);
 
       f.render_widget(status_right, status_chunks[STATUSBAR_RIGHT_INDEX]);
@@ -129,9 +223,9 @@ where
 
     let cursor = match greeter.mode {
       Mode::Command => self::command::draw(&mut greeter, f).ok(),
-      Mode::Sessions => greeter.sessions.draw(&greeter, f).ok(),
-      Mode::Power => greeter.powers.draw(&greeter, f).ok(),
-      Mode::Users => greeter.users.draw(&greeter, f).ok(),
+      Mode::Sessions => greeter.sessions.draw(&greeter, f, greeter.max_sessions_displayed).ok(),
+      Mode::Power => greeter.powers.draw(&greeter, f, None).ok(),
+      Mode::Users => greeter.users.draw(&greeter, f, None).ok(),
       Mode::Processing => self::processing::draw(&mut greeter, f).ok(),
       _ => self::prompt::draw(&mut greeter, f).ok(),
     };
@@ -141,22 +235,116 @@ where
         f.set_cursor(cursor.0 - 1, cursor.1 - 1);
       }
     }
+
+    if transitioning {
+      let area = f.size();
+
+      f.buffer_mut().set_style(area, Style::default().add_modifier(Modifier::DIM));
+    }
   })?;
 
+  if let Some(style) = get_cursor_style(&greeter, hide_cursor) {
+    execute!(io::stdout(), style)?;
+  }
+
   io::stdout().flush()?;
 
   Ok(())
 }
 
+// Records the screen being drawn, and tells whether the frame should be dimmed
+// because that screen was just switched to. The next frame is drawn normally.
+fn is_transitioning(greeter: &mut Greeter) -> bool {
+  let switched = matches!(greeter.drawn_mode, Some(mode) if mode != greeter.mode);
+
+  greeter.drawn_mode = Some(greeter.mode);
+  greeter.transition_effect && switched
+}
+
+// Draws the available sessions as a row of tabs, highlighting the one that
+// will be started.
+fn draw_session_tabs(greeter: &Greeter, f: &mut Frame, area: Rect) {
+  let theme = &greeter.theme;
+
+  let chunks = Layout::default()
+    .direction(Direction::Horizontal)
+    .constraints([Constraint::Length(greeter.window_padding()), Constraint::Min(1), Constraint::Length(greeter.window_padding())].as_ref())
+    .split(area);
+
+  let selected = match greeter.session_source {
+    SessionSource::Session(index) => index,
+    _ => usize::MAX,
+  };
+
+  let titles = greeter.sessions.options.iter().map(|session| session.name.clone());
+  let tabs = Tabs::new(titles)
+    .select(selected)
+    .style(theme.of(&[Themed::Action]))
+    .highlight_style(theme.of(&[Themed::ActionButton]).add_modifier(Modifier::REVERSED));
+
+  f.render_widget(tabs, chunks[1]);
+}
+
+// Returns the area in which the clock and status bar are drawn, centered on
+// the screen if a maximum width was configured.
+fn get_content_area(greeter: &Greeter, area: Rect) -> Rect {
+  match greeter.max_content_width() {
+    Some(width) if width < area.width => Rect::new(area.x + (area.width - width) / 2, area.y, width, area.height),
+    _ => area,
+  }
+}
+
+// Whether the caps lock indicator should be displayed. The provided probe is
+// not run at all if the indicator is disabled.
+fn should_show_capslock<F>(greeter: &Greeter, probe: F) -> bool
+where
+  F: FnOnce() -> bool,
+{
+  greeter.capslock && probe()
+}
+
+// Returns the caps lock indicator, highlighted as a warning while a secret is
+// being typed.
+fn capslock_label<'s>(greeter: &Greeter, theme: &Theme) -> Span<'s> {
+  if greeter.mode == Mode::Password && greeter.asking_for_secret {
+    Span::styled(fl!("status_caps"), theme.of(&[Themed::CapsWarning]).add_modifier(Modifier::REVERSED | Modifier::BOLD))
+  } else {
+    status_label(theme, fl!("status_caps"))
+  }
+}
+
+// Formats the footer configured by the user, replacing `%h` with the hostname
+// and `%v` with the version of tuigreet.
+fn get_footer(greeter: &Greeter) -> Option<String> {
+  greeter
+    .footer
+    .as_ref()
+    .filter(|footer| !footer.is_empty())
+    .map(|footer| footer.replace("%h", &get_hostname()).replace("%v", env!("VERSION")))
+}
+
 fn get_time(greeter: &Greeter) -> String {
-  let format = match &greeter.time_format {
-    Some(format) => Cow::Borrowed(format),
-    None => Cow::Owned(fl!("date")),
+  let format = match (&greeter.time_format, greeter.locale_time_format) {
+    (Some(format), _) => Cow::Borrowed(format.as_str()),
+    (None, true) => Cow::Owned(get_locale_time_format(greeter.locale)),
+    (None, false) => Cow::Owned(fl!("date")),
   };
 
   Local::now().format_localized(&format, greeter.locale).to_string()
 }
 
+// Builds a date and time format from the locale data: its date representation,
+// followed by a 12 or 24-hour clock, depending on which one its time
+// representation uses. Seconds are left out.
+fn get_locale_time_format(locale: Locale) -> String {
+  let afternoon = Utc.with_ymd_and_hms(2000, 1, 1, 13, 0, 0).unwrap().format_localized("%X", locale).to_string();
+
+  match afternoon.contains("13") {
+    true => "%x - %H:%M".to_string(),
+    false => "%x - %I:%M %p".to_string(),
+  }
+}
+
 fn status_label<'s, S>(theme: &Theme, text: S) -> Span<'s>
 where
   S: Into<String>,
@@ -186,6 +374,15 @@ where
   Span::from(buttonize(&text.into())).style(style)
 }
 
+// Returns the span inserted between groups of the status bar, which is a
+// single space unless a custom separator was configured.
+fn status_separator<'s>(greeter: &Greeter, theme: &Theme) -> Span<'s> {
+  match greeter.status_separator {
+    Some(ref separator) => Span::styled(separator.clone(), theme.of(&[Themed::Separator])),
+    None => Span::from(" "),
+  }
+}
+
 fn prompt_value<'s, S>(theme: &Theme, text: Option<S>) -> Span<'s>
 where
   S: Into<String>,
@@ -195,3 +392,147 @@ where
     None => Span::from(""),
   }
 }
+
+#[cfg(test)]
+mod test {
+  use chrono::Locale;
+  use tui::{
+    backend::TestBackend,
+    style::{Color, Modifier},
+    Terminal,
+  };
+
+  use crate::{
+    ui::{
+      common::{menu::Menu, style::Theme},
+      sessions::{Session, SessionSource},
+    },
+    Greeter, Mode,
+  };
+
+  use super::{capslock_label, draw_session_tabs, get_locale_time_format, is_transitioning, should_show_capslock};
+
+  #[test]
+  fn transition_on_mode_switch() {
+    let mut greeter = Greeter::default();
+    greeter.mode = Mode::Username;
+
+    assert!(!is_transitioning(&mut greeter));
+
+    greeter.mode = Mode::Sessions;
+
+    assert!(!is_transitioning(&mut greeter));
+    assert_eq!(greeter.drawn_mode, Some(Mode::Sessions));
+
+    greeter.transition_effect = true;
+    greeter.mode = Mode::Username;
+
+    assert!(is_transitioning(&mut greeter));
+    assert_eq!(greeter.drawn_mode, Some(Mode::Username));
+    assert!(!is_transitioning(&mut greeter));
+  }
+
+  #[test]
+  fn session_tabs() {
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--sessions-tabs"]).ok();
+    greeter.sessions_tabs = true;
+    greeter.session_source = SessionSource::Session(1);
+    greeter.sessions = Menu::<Session> {
+      title: "Sessions".into(),
+      selected: 1,
+      options: vec![
+        Session {
+          name: "Sway".into(),
+          ..Default::default()
+        },
+        Session {
+          name: "GNOME".into(),
+          ..Default::default()
+        },
+      ],
+      expand: None,
+    };
+
+    assert!(greeter.show_session_tabs());
+
+    let mut terminal = Terminal::new(TestBackend::new(40, 1)).unwrap();
+
+    terminal.draw(|f| draw_session_tabs(&greeter, f, f.size())).unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let row: String = (0..40).map(|x| buffer.get(x, 0).symbol().to_string()).collect();
+    let sway = row.find("Sway").unwrap() as u16;
+    let gnome = row.find("GNOME").unwrap() as u16;
+
+    assert!(sway < gnome);
+    assert!(!buffer.get(sway, 0).modifier.contains(Modifier::REVERSED));
+    assert!(buffer.get(gnome, 0).modifier.contains(Modifier::REVERSED));
+
+    greeter.sessions.options.truncate(1);
+
+    assert!(!greeter.show_session_tabs());
+  }
+
+  #[test]
+  fn capslock_enabled() {
+    let greeter = Greeter::default();
+
+    assert!(should_show_capslock(&greeter, || true));
+    assert!(!should_show_capslock(&greeter, || false));
+  }
+
+  #[test]
+  fn capslock_disabled() {
+    let mut greeter = Greeter::default();
+    greeter.capslock = false;
+
+    assert!(!should_show_capslock(&greeter, || panic!("caps lock should not be polled")));
+  }
+
+  #[test]
+  fn capslock_warning_on_secret() {
+    let mut greeter = Greeter::default();
+    greeter.mode = Mode::Username;
+
+    let label = capslock_label(&greeter, &Theme::default());
+
+    assert_eq!(label.style.fg, None);
+
+    greeter.mode = Mode::Password;
+    greeter.asking_for_secret = true;
+
+    let label = capslock_label(&greeter, &Theme::default());
+
+    assert_eq!(label.style.fg, Some(Color::Red));
+    assert!(label.style.add_modifier.contains(Modifier::BOLD));
+
+    let label = capslock_label(&greeter, &Theme::parse("capswarning=yellow"));
+
+    assert_eq!(label.style.fg, Some(Color::Yellow));
+  }
+
+  #[test]
+  fn locale_time_format_12_hours() {
+    assert_eq!(get_locale_time_format(Locale::en_US), "%x - %I:%M %p");
+  }
+
+  #[test]
+  fn locale_time_format_24_hours() {
+    assert_eq!(get_locale_time_format(Locale::POSIX), "%x - %H:%M");
+    assert_eq!(get_locale_time_format(Locale::de_DE), "%x - %H:%M");
+    assert_eq!(get_locale_time_format(Locale::fr_FR), "%x - %H:%M");
+  }
+
+  #[test]
+  fn default_time_formats_without_seconds() {
+    let locales = [Locale::POSIX, Locale::en_US, Locale::en_GB, Locale::de_DE, Locale::fr_FR, Locale::ja_JP];
+    let formats = locales.iter().map(|locale| get_locale_time_format(*locale)).chain(std::iter::once(fl!("date")));
+
+    for format in formats {
+      for specifier in ["%S", "%T", "%X", "%r", "%s", "%c"].iter() {
+        assert!(!format.contains(specifier), "{} contains {}", format, specifier);
+      }
+    }
+  }
+}
@@ -1,6 +1,10 @@
 use std::borrow::Cow;
 
-use crate::{power::PowerOption, ui::common::menu::MenuItem};
+use crate::{
+  power::{default_command, PowerOption},
+  ui::common::menu::MenuItem,
+  Greeter,
+};
 
 #[derive(SmartDefault, Clone)]
 pub struct Power {
@@ -10,7 +14,18 @@ pub struct Power {
 }
 
 impl MenuItem for Power {
-  fn format(&self) -> Cow<'_, str> {
+  fn format(&self, _greeter: &Greeter) -> Cow<'_, str> {
     Cow::Borrowed(&self.label)
   }
+
+  fn hint(&self, greeter: &Greeter) -> Option<Cow<'_, str>> {
+    if !greeter.show_power_commands {
+      return None;
+    }
+
+    match self.command {
+      Some(ref command) => Some(Cow::Borrowed(command)),
+      None => Some(Cow::Borrowed(default_command(self.action))),
+    }
+  }
 }
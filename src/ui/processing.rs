@@ -28,7 +28,7 @@ pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn
   let constraints = [Constraint::Length(1)];
 
   let chunks = Layout::default().direction(Direction::Vertical).constraints(constraints.as_ref()).split(frame);
-  let text = Span::from(fl!("wait"));
+  let text = Span::from(get_processing_message(greeter));
   let paragraph = Paragraph::new(text).alignment(Alignment::Center);
 
   f.render_widget(paragraph, chunks[0]);
@@ -36,3 +36,18 @@ pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn
 
   Ok((1, 1))
 }
+
+// Returns the text displayed while the session is starting, substituting the
+// command actually being started, or the selected session until it is known, if
+// a custom message was provided.
+fn get_processing_message(greeter: &Greeter) -> String {
+  let session = match greeter.session_starting {
+    Some((ref command, _)) => command.as_str(),
+    None => greeter.session_source.label(greeter).unwrap_or_default(),
+  };
+
+  match greeter.session_starting_message {
+    Some(ref message) => message.replace("%s", session),
+    None => greeter.working_text(),
+  }
+}
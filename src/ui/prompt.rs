@@ -10,14 +10,16 @@ use tui::{
 use crate::{
   info::get_hostname,
   ui::{prompt_value, util::*, Frame},
-  GreetAlign, Greeter, Mode, SecretDisplay,
+  GreetAlign, Greeter, Mode, PromptOrder, SecretDisplay,
 };
 
 use super::common::style::Themed;
 
 const GREETING_INDEX: usize = 0;
-const USERNAME_INDEX: usize = 1;
-const ANSWER_INDEX: usize = 3;
+const FIRST_PROMPT_INDEX: usize = 1;
+const SECOND_PROMPT_INDEX: usize = 3;
+
+const PROGRESS_WIDTH: usize = 8;
 
 pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn Error>> {
   let theme = &greeter.theme;
@@ -49,18 +51,39 @@ pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn
 
   let (message, message_height) = get_message_height(greeter, container_padding, 1);
   let (greeting, greeting_height) = get_greeting_height(greeter, container_padding, 0);
+  let (full_width_greeting, full_width_greeting_height) = get_full_width_greeting(greeter, size);
+
+  if let Some(greeting) = full_width_greeting {
+    // On a short terminal, only the rows left above the container are used, so
+    // the greeting is never drawn over it.
+    let height = full_width_greeting_height.min(y.saturating_sub(size.y));
+    let rows = height.saturating_sub(greeter.greeting_prompt_spacing());
+
+    if rows > 0 {
+      let window_padding = greeter.window_padding();
+      let greeting_label = greeting.alignment(greeting_alignment).style(theme.of(&[Themed::Greet]));
+
+      f.render_widget(greeting_label, Rect::new(size.x + window_padding, y - height, size.width.saturating_sub(2 * window_padding), rows));
+    }
+  }
 
   let should_display_answer = greeter.mode == Mode::Password;
 
-  let constraints = [
+  let (username_index, answer_index) = match greeter.prompt_order {
+    PromptOrder::UserFirst => (FIRST_PROMPT_INDEX, SECOND_PROMPT_INDEX),
+    PromptOrder::PasswordFirst => (SECOND_PROMPT_INDEX, FIRST_PROMPT_INDEX),
+  };
+
+  let mut constraints = [
     Constraint::Length(greeting_height),                                        // Greeting
-    Constraint::Length(1),                                                      // Username
+    Constraint::Length(1),                                                      // First prompt
     Constraint::Length(if should_display_answer { prompt_padding } else { 0 }), // Prompt padding
-    Constraint::Length(if should_display_answer { 1 } else { 0 }),              // Answer
+    Constraint::Length(1),                                                      // Second prompt
   ];
 
+  constraints[answer_index] = Constraint::Length(if should_display_answer { 1 } else { 0 });
+
   let chunks = Layout::default().direction(Direction::Vertical).constraints(constraints.as_ref()).split(frame);
-  let cursor = chunks[USERNAME_INDEX];
 
   if let Some(greeting) = greeting {
     let greeting_label = greeting.alignment(greeting_alignment).style(theme.of(&[Themed::Greet]));
@@ -78,60 +101,65 @@ pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn
     Paragraph::new(username_text)
   };
 
-  let username = greeter.username.get();
-  let username_value_text = Span::from(username);
+  let username = match greeter.show_username_and_name {
+    true => greeter.username.get_with_value(),
+    false => greeter.username.get().into(),
+  };
+
+  let username_length = username.chars().count();
+  let username_width = get_input_width(greeter, width, &Some(fl!("username")));
+
+  // Only the field being edited is scrolled to follow the cursor.
+  let username_scroll = match greeter.mode {
+    Mode::Username => get_input_scroll(greeter, username_length, username_width),
+    _ => 0,
+  };
+
+  let username_value_text = Span::from(get_visible_input(&username, username_scroll));
   let username_value = Paragraph::new(username_value_text).style(theme.of(&[Themed::Input]));
 
   match greeter.mode {
     Mode::Username | Mode::Password | Mode::Action => {
-      f.render_widget(username_label, chunks[USERNAME_INDEX]);
+      f.render_widget(username_label, chunks[username_index]);
 
       if !greeter.user_menu || !greeter.username.value.is_empty() {
         f.render_widget(
           username_value,
-          Rect::new(
-            1 + chunks[USERNAME_INDEX].x + fl!("username").chars().count() as u16,
-            chunks[USERNAME_INDEX].y,
-            get_input_width(greeter, width, &Some(fl!("username"))),
-            1,
-          ),
+          Rect::new(1 + chunks[username_index].x + fl!("username").chars().count() as u16, chunks[username_index].y, username_width, 1),
         );
       }
 
-      let answer_text = if greeter.working { Span::from(fl!("wait")) } else { prompt_value(theme, greeter.prompt.as_ref()) };
+      let answer_text = if greeter.working {
+        Span::from(greeter.working_text())
+      } else {
+        prompt_value(theme, greeter.prompt.as_ref())
+      };
 
       let answer_label = Paragraph::new(answer_text);
+      let answer_width = get_input_width(greeter, width, &greeter.prompt);
 
       if greeter.mode == Mode::Password || greeter.previous_mode == Mode::Password {
-        f.render_widget(answer_label, chunks[ANSWER_INDEX]);
+        f.render_widget(answer_label, chunks[answer_index]);
 
         if !greeter.asking_for_secret || greeter.secret_display.show() {
           let value = match (greeter.asking_for_secret, &greeter.secret_display) {
-            (true, SecretDisplay::Character(pool)) => {
-              if pool.chars().count() == 1 {
-                pool.repeat(greeter.buffer.chars().count())
-              } else {
-                let mut rng = StdRng::seed_from_u64(0);
+            (true, SecretDisplay::Character(pool)) => get_masked_secret(pool, &greeter.buffer, greeter.secret_mask_seed),
 
-                greeter.buffer.chars().map(|_| pool.chars().nth(rng.gen_range(0..pool.chars().count())).unwrap()).collect()
-              }
-            }
+            (true, SecretDisplay::Progress) => get_progress_bar(greeter.buffer.chars().count()),
 
             _ => greeter.buffer.clone(),
           };
 
-          let answer_value_text = Span::from(value);
+          let answer_scroll = match (greeter.mode, &greeter.secret_display) {
+            (Mode::Password, SecretDisplay::Progress) if greeter.asking_for_secret => 0,
+            (Mode::Password, _) => get_input_scroll(greeter, greeter.buffer.chars().count(), answer_width),
+            _ => 0,
+          };
+
+          let answer_value_text = Span::from(get_visible_input(&value, answer_scroll));
           let answer_value = Paragraph::new(answer_value_text).style(theme.of(&[Themed::Input]));
 
-          f.render_widget(
-            answer_value,
-            Rect::new(
-              chunks[ANSWER_INDEX].x + greeter.prompt_width() as u16,
-              chunks[ANSWER_INDEX].y,
-              get_input_width(greeter, width, &greeter.prompt),
-              1,
-            ),
-          );
+          f.render_widget(answer_value, Rect::new(chunks[answer_index].x + greeter.prompt_width() as u16, chunks[answer_index].y, answer_width, 1));
         }
       }
 
@@ -145,25 +173,157 @@ pub fn draw(greeter: &mut Greeter, f: &mut Frame) -> Result<(u16, u16), Box<dyn
     _ => {}
   }
 
+  // Cursor coordinates are 1-based, on the row of the prompt being edited.
   match greeter.mode {
     Mode::Username => {
-      let username_length = greeter.username.get().chars().count();
-      let offset = get_cursor_offset(greeter, username_length);
+      let cursor = chunks[username_index];
+      let offset = get_cursor_offset(greeter, username_length) as usize - username_scroll;
 
-      Ok((2 + cursor.x + fl!("username").chars().count() as u16 + offset as u16, USERNAME_INDEX as u16 + cursor.y))
+      Ok((2 + cursor.x + fl!("username").chars().count() as u16 + offset as u16, 1 + cursor.y))
     }
 
     Mode::Password => {
+      let cursor = chunks[answer_index];
       let answer_length = greeter.buffer.chars().count();
-      let offset = get_cursor_offset(greeter, answer_length);
+      let scroll = get_input_scroll(greeter, answer_length, get_input_width(greeter, width, &greeter.prompt));
+      let offset = get_cursor_offset(greeter, answer_length) as usize - scroll;
 
       if greeter.asking_for_secret && !greeter.secret_display.show() {
-        Ok((1 + cursor.x + greeter.prompt_width() as u16, ANSWER_INDEX as u16 + prompt_padding + cursor.y - 1))
+        Ok((1 + cursor.x + greeter.prompt_width() as u16, 1 + cursor.y))
+      } else if greeter.asking_for_secret && matches!(greeter.secret_display, SecretDisplay::Progress) {
+        Ok((1 + cursor.x + (greeter.prompt_width() + PROGRESS_WIDTH) as u16, 1 + cursor.y))
       } else {
-        Ok((1 + cursor.x + greeter.prompt_width() as u16 + offset as u16, ANSWER_INDEX as u16 + prompt_padding + cursor.y - 1))
+        Ok((1 + cursor.x + greeter.prompt_width() as u16 + offset as u16, 1 + cursor.y))
       }
     }
 
     _ => Ok((1, 1)),
   }
 }
+
+// Redacts a secret with characters from the provided pool, picked from a
+// random generator seeded with `seed` so the mask stays stable while typing.
+fn get_masked_secret(pool: &str, secret: &str, seed: u64) -> String {
+  if pool.chars().count() == 1 {
+    pool.repeat(secret.chars().count())
+  } else {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    secret.chars().map(|_| pool.chars().nth(rng.gen_range(0..pool.chars().count())).unwrap()).collect()
+  }
+}
+
+// Renders a fixed-width bar with an indicator advancing for every typed
+// character, wrapping around, so the length of the secret is not revealed.
+fn get_progress_bar(length: usize) -> String {
+  (0..PROGRESS_WIDTH)
+    .map(|index| match length {
+      0 => '-',
+      _ if index == (length - 1) % PROGRESS_WIDTH => '*',
+      _ => '-',
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use tui::{backend::TestBackend, Terminal};
+
+  use crate::{ui::common::masked::MaskedString, Greeter, Mode};
+
+  use super::{draw, get_masked_secret, get_progress_bar};
+
+  #[test]
+  fn long_input_keeps_cursor_in_bounds() {
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--width", "40"]).ok();
+    greeter.mode = Mode::Username;
+    greeter.username = MaskedString::from(format!("{}END", "a".repeat(100)), None);
+
+    let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+    let mut cursor = (0, 0);
+
+    terminal
+      .draw(|f| {
+        cursor = draw(&mut greeter, f).unwrap();
+      })
+      .unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let row: String = (0..100).map(|x| buffer.get(x, cursor.1 - 1).symbol().to_string()).collect();
+
+    // The container spans columns 30 to 69, including its borders.
+    assert!(cursor.0 - 1 < 69);
+    assert!(row.contains("aaaEND"));
+  }
+
+  fn render(greeter: &mut Greeter) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+
+    terminal
+      .draw(|f| {
+        let _ = draw(greeter, f);
+      })
+      .unwrap();
+
+    let buffer = terminal.backend().buffer();
+
+    (0..30).map(|y| (0..120).map(|x| buffer.get(x, y).symbol().to_string()).collect()).collect()
+  }
+
+  #[test]
+  fn full_width_greeting() {
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--width", "40"]).ok();
+    greeter.mode = Mode::Username;
+    greeter.greeting = Some("Welcome ".repeat(10));
+
+    let rows = render(&mut greeter);
+    let container_top = rows.iter().position(|row| row.contains('┌')).unwrap();
+
+    // The greeting is wrapped inside of the container.
+    assert!(rows.iter().all(|row| !row.contains(&"Welcome ".repeat(5))));
+    assert!(rows.iter().skip(container_top).any(|row| row.contains("Welcome")));
+
+    greeter.greeting_full_width = true;
+
+    let rows = render(&mut greeter);
+    let container_top = rows.iter().position(|row| row.contains('┌')).unwrap();
+    let greeting = rows.iter().position(|row| row.contains(&"Welcome ".repeat(9))).unwrap();
+
+    // It now spans the screen on a single line, right above the container.
+    assert_eq!(greeting + 2, container_top);
+    assert!(rows.iter().skip(container_top).all(|row| !row.contains("Welcome")));
+
+    // Without enough room above the container, it is not drawn over it.
+    greeter.greeting = Some("Welcome\n".repeat(40));
+
+    let rows = render(&mut greeter);
+
+    assert!(rows[0].contains('┌'));
+    assert!(rows.iter().all(|row| !row.contains("Welcome")));
+  }
+
+  #[test]
+  fn masked_secret_single_char() {
+    assert_eq!(get_masked_secret("*", "hunter2", 0), "*******");
+    assert_eq!(get_masked_secret("*", "hunter2", 42), "*******");
+  }
+
+  #[test]
+  fn masked_secret_seeds() {
+    let pool = "abcdefghijklmnopqrstuvwxyz";
+
+    assert_eq!(get_masked_secret(pool, "hunter2", 0), get_masked_secret(pool, "hunter2", 0));
+    assert_ne!(get_masked_secret(pool, "hunter2", 0), get_masked_secret(pool, "hunter2", 1));
+  }
+
+  #[test]
+  fn progress_bar() {
+    assert_eq!(get_progress_bar(0), "--------");
+    assert_eq!(get_progress_bar(1), "*-------");
+    assert_eq!(get_progress_bar(3), "--*-----");
+    assert_eq!(get_progress_bar(8), "-------*");
+    assert_eq!(get_progress_bar(9), "*-------");
+  }
+}
@@ -3,9 +3,11 @@ use std::{
   path::{Path, PathBuf},
 };
 
+use chrono::{DateTime, Local};
+
 use crate::Greeter;
 
-use super::common::menu::MenuItem;
+use super::{common::menu::MenuItem, util::format_relative_time};
 
 // SessionSource models the selected session and where it comes from.
 //
@@ -98,12 +100,21 @@ pub struct Session {
   pub path: Option<PathBuf>,
   // Desktop names as defined with the `DesktopNames` desktop file property
   pub xdg_desktop_names: Option<String>,
+  // When the session was last started, if known and requested.
+  pub last_used: Option<DateTime<Local>>,
 }
 
 impl MenuItem for Session {
-  fn format(&self) -> Cow<'_, str> {
+  fn format(&self, _greeter: &Greeter) -> Cow<'_, str> {
     Cow::Borrowed(&self.name)
   }
+
+  fn hint(&self, greeter: &Greeter) -> Option<Cow<'_, str>> {
+    match greeter.show_session_last_used {
+      true => self.last_used.map(|time| Cow::Owned(format_relative_time(Local::now() - time))),
+      false => None,
+    }
+  }
 }
 
 impl Session {
@@ -164,11 +175,12 @@ mod test {
           ..Default::default()
         },
       ],
+      expand: None,
     };
 
     let session = Session::from_path(&greeter, "/Session2Path");
 
-    assert!(matches!(session, Some(_)));
+    assert!(session.is_some());
     assert_eq!(session.unwrap().name, "Session2");
     assert_eq!(session.unwrap().session_type, SessionType::X11);
   }
@@ -188,18 +200,19 @@ mod test {
         path: Some("/Session1Path".into()),
         ..Default::default()
       }],
+      expand: None,
     };
 
     let session = Session::from_path(&greeter, "/Session2Path");
 
-    assert!(matches!(session, None));
+    assert!(session.is_none());
   }
 
   #[test]
   fn no_session() {
     let greeter = Greeter::default();
 
-    assert!(matches!(Session::get_selected(&greeter), None));
+    assert!(Session::get_selected(&greeter).is_none());
   }
 
   #[test]
@@ -226,11 +239,12 @@ mod test {
           ..Default::default()
         },
       ],
+      expand: None,
     };
 
     let session = Session::get_selected(&greeter);
 
-    assert!(matches!(session, Some(_)));
+    assert!(session.is_some());
     assert_eq!(session.unwrap().name, "Session2");
     assert_eq!(session.unwrap().session_type, SessionType::X11);
   }
@@ -259,11 +273,12 @@ mod test {
           ..Default::default()
         },
       ],
+      expand: None,
     };
 
     let session = Session::get_selected(&greeter);
 
-    assert!(matches!(session, Some(_)));
+    assert!(session.is_some());
     assert_eq!(session.unwrap().name, "Session");
     assert_eq!(session.unwrap().session_type, SessionType::X11);
     assert_eq!(session.unwrap().command, "Session2Cmd");
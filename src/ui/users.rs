@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use super::common::menu::MenuItem;
+use crate::{ui::common::menu::MenuItem, Greeter};
 
 #[derive(Default, Clone)]
 pub struct User {
@@ -9,10 +9,66 @@ pub struct User {
 }
 
 impl MenuItem for User {
-  fn format(&self) -> Cow<'_, str> {
-    match &self.name {
-      Some(name) => Cow::Owned(format!("{name} ({})", self.username)),
-      None => Cow::Borrowed(&self.username),
+  fn format(&self, greeter: &Greeter) -> Cow<'_, str> {
+    match (&self.name, &greeter.user_menu_format) {
+      (Some(name), Some(template)) => Cow::Owned(format_user(template, name, &self.username)),
+      (Some(name), None) => Cow::Owned(format!("{name} ({})", self.username)),
+      (None, _) => Cow::Borrowed(&self.username),
     }
   }
 }
+
+// Replaces `%n` with the full name and `%u` with the username in a template.
+// Substituted values are not themselves searched for placeholders.
+fn format_user(template: &str, name: &str, username: &str) -> String {
+  let mut output = String::new();
+  let mut chars = template.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '%' => match chars.next() {
+        Some('n') => output.push_str(name),
+        Some('u') => output.push_str(username),
+        Some(other) => {
+          output.push('%');
+          output.push(other);
+        }
+        None => output.push('%'),
+      },
+
+      c => output.push(c),
+    }
+  }
+
+  output
+}
+
+#[cfg(test)]
+mod test {
+  use crate::{ui::common::menu::MenuItem, Greeter};
+
+  use super::User;
+
+  #[test]
+  fn user_menu_format() {
+    let mut greeter = Greeter::default();
+
+    let named = User {
+      username: "apognu".to_string(),
+      name: Some("Antoine POPINEAU".to_string()),
+    };
+
+    let unnamed = User {
+      username: "root".to_string(),
+      name: None,
+    };
+
+    assert_eq!(named.format(&greeter), "Antoine POPINEAU (apognu)");
+    assert_eq!(unnamed.format(&greeter), "root");
+
+    greeter.user_menu_format = Some("%u — %n (100%)".to_string());
+
+    assert_eq!(named.format(&greeter), "apognu — Antoine POPINEAU (100%)");
+    assert_eq!(unnamed.format(&greeter), "root");
+  }
+}
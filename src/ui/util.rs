@@ -1,12 +1,27 @@
+use std::borrow::Cow;
+
 use ansi_to_tui::IntoText;
+use crossterm::cursor::SetCursorStyle;
 use tui::{
   prelude::Rect,
   text::Text,
   widgets::{Paragraph, Wrap},
 };
 
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
 use crate::{Greeter, Mode};
 
+// Formats how long ago something happened, in the largest relevant unit.
+pub fn format_relative_time(elapsed: chrono::Duration) -> String {
+  match elapsed {
+    elapsed if elapsed.num_days() > 0 => fl!("time_days_ago", count = elapsed.num_days()),
+    elapsed if elapsed.num_hours() > 0 => fl!("time_hours_ago", count = elapsed.num_hours()),
+    elapsed if elapsed.num_minutes() > 0 => fl!("time_minutes_ago", count = elapsed.num_minutes()),
+    _ => fl!("time_just_now"),
+  }
+}
+
 pub fn titleize(message: &str) -> String {
   format!(" {message} ")
 }
@@ -15,6 +30,35 @@ pub fn buttonize(message: &str) -> String {
   format!(" {message}")
 }
 
+// Shortens a text so it fits in the given number of columns, replacing its end
+// with an ellipsis if it had to be cut.
+pub fn truncate(text: &str, width: usize) -> Cow<'_, str> {
+  if text.width() <= width {
+    return Cow::Borrowed(text);
+  }
+
+  if width == 0 {
+    return Cow::Borrowed("");
+  }
+
+  let mut columns = 0;
+  let mut truncated = String::new();
+
+  for c in text.chars() {
+    columns += c.width().unwrap_or(0);
+
+    if columns > width - 1 {
+      break;
+    }
+
+    truncated.push(c);
+  }
+
+  truncated.push('…');
+
+  Cow::Owned(truncated)
+}
+
 // Determinew whether the cursor should be shown or hidden from the current
 // mode and configuration. Usually, we will show the cursor only when expecting
 // text entries from the user.
@@ -30,6 +74,16 @@ pub fn should_hide_cursor(greeter: &Greeter) -> bool {
     || greeter.mode == Mode::Action
 }
 
+// Determines which cursor style, if any, should be requested from the terminal
+// when the cursor is displayed. If the user did not ask for a specific style,
+// the terminal's configuration is left untouched.
+pub fn get_cursor_style(greeter: &Greeter, hide_cursor: bool) -> Option<SetCursorStyle> {
+  match (hide_cursor, greeter.steady_cursor) {
+    (false, true) => Some(SetCursorStyle::SteadyBlock),
+    _ => None,
+  }
+}
+
 // Computes the height of the main window where we display content, depending on
 // the mode and spacing configuration.
 //
@@ -66,9 +120,15 @@ pub fn get_height(greeter: &Greeter) -> u16 {
 pub fn get_rect_bounds(greeter: &Greeter, area: Rect, items: usize) -> (u16, u16, u16, u16) {
   let width = greeter.width();
   let height: u16 = get_height(greeter) + items as u16;
+  let (_, greeting_height) = get_full_width_greeting(greeter, area);
 
+  // A full-width greeting is centered along with the main window, above it.
   let x = if width < area.width { (area.width - width) / 2 } else { 0 };
-  let y = if height < area.height { (area.height - height) / 2 } else { 0 };
+  let y = if height + greeting_height < area.height {
+    (area.height - height - greeting_height) / 2 + greeting_height
+  } else {
+    0
+  };
 
   let (x, width) = if (x + width) >= area.width { (0, area.width) } else { (x, width) };
   let (y, height) = if (y + height) >= area.height { (0, area.height) } else { (y, height) };
@@ -89,6 +149,19 @@ pub fn get_input_width(greeter: &Greeter, width: u16, label: &Option<String>) ->
   width - label_width as u16 - 4 - 1
 }
 
+// Returns how many characters of an input should be skipped when drawing it, so
+// that the cursor stays within the `width` columns of the field.
+pub fn get_input_scroll(greeter: &Greeter, length: usize, width: u16) -> usize {
+  let offset = (length as i16 + greeter.cursor_offset).clamp(0, length as i16) as usize;
+
+  offset.saturating_sub((width as usize).saturating_sub(1))
+}
+
+// Returns the part of an input that is visible after scrolling.
+pub fn get_visible_input(value: &str, scroll: usize) -> String {
+  value.chars().skip(scroll).collect()
+}
+
 pub fn get_cursor_offset(greeter: &mut Greeter, length: usize) -> i16 {
   let mut offset = length as i16 + greeter.cursor_offset;
 
@@ -105,25 +178,53 @@ pub fn get_cursor_offset(greeter: &mut Greeter, length: usize) -> i16 {
   offset
 }
 
-pub fn get_greeting_height(greeter: &Greeter, padding: u16, fallback: u16) -> (Option<Paragraph>, u16) {
-  if let Some(greeting) = &greeter.greeting {
-    let width = greeter.width();
+fn get_greeting(greeter: &Greeter) -> Option<Paragraph<'_>> {
+  greeter.greeting.as_ref().map(|greeting| {
+    let greeting = if greeter.greeting_trim { greeting.trim() } else { greeting.as_str() };
 
-    let text = match greeting.clone().trim().into_text() {
+    let text = match greeting.into_text() {
       Ok(text) => text,
       Err(_) => Text::raw(greeting),
     };
 
-    let paragraph = Paragraph::new(text.clone()).wrap(Wrap { trim: false });
-    let height = paragraph.line_count(width - (2 * padding)) + 1;
+    Paragraph::new(text).wrap(Wrap { trim: false })
+  })
+}
 
-    (Some(paragraph), height as u16)
-  } else {
-    (None, fallback)
+pub fn get_greeting_height(greeter: &Greeter, padding: u16, fallback: u16) -> (Option<Paragraph<'_>>, u16) {
+  match get_greeting(greeter) {
+    Some(paragraph) if !greeter.greeting_full_width => {
+      let width = greeter.width();
+      let height = paragraph.line_count(width - (2 * padding)) + greeter.greeting_prompt_spacing() as usize;
+
+      (Some(paragraph), height as u16)
+    }
+
+    _ => (None, fallback),
+  }
+}
+
+// Returns the greeting displayed above the main window, across the screen
+// minus the window padding, if configured to, and the rows it takes, including
+// the spacing below it. Only the prompt displays the greeting.
+pub fn get_full_width_greeting(greeter: &Greeter, area: Rect) -> (Option<Paragraph<'_>>, u16) {
+  if !greeter.greeting_full_width || !matches!(greeter.mode, Mode::Username | Mode::Password | Mode::Action) {
+    return (None, 0);
+  }
+
+  match get_greeting(greeter) {
+    Some(paragraph) => {
+      let width = area.width.saturating_sub(2 * greeter.window_padding());
+      let height = paragraph.line_count(width) + greeter.greeting_prompt_spacing() as usize;
+
+      (Some(paragraph), height as u16)
+    }
+
+    None => (None, 0),
   }
 }
 
-pub fn get_message_height(greeter: &Greeter, padding: u16, fallback: u16) -> (Option<Paragraph>, u16) {
+pub fn get_message_height(greeter: &Greeter, padding: u16, fallback: u16) -> (Option<Paragraph<'_>>, u16) {
   if let Some(message) = &greeter.message {
     let width = greeter.width();
     let paragraph = Paragraph::new(message.trim_end()).wrap(Wrap { trim: true });
@@ -137,6 +238,7 @@ pub fn get_message_height(greeter: &Greeter, padding: u16, fallback: u16) -> (Op
 
 #[cfg(test)]
 mod test {
+  use crossterm::{cursor::SetCursorStyle, Command};
   use tui::{
     prelude::Rect,
     style::{Color, Style},
@@ -145,11 +247,37 @@ mod test {
   };
 
   use crate::{
-    ui::util::{get_greeting_height, get_height},
+    ui::util::{get_cursor_style, get_greeting_height, get_height},
     Greeter, Mode,
   };
 
-  use super::{get_input_width, get_rect_bounds};
+  use super::{format_relative_time, get_input_scroll, get_input_width, get_rect_bounds, get_visible_input, truncate};
+
+  fn cursor_sequence(style: Option<SetCursorStyle>) -> Option<String> {
+    style.map(|style| {
+      let mut sequence = String::new();
+      let _ = style.write_ansi(&mut sequence);
+
+      sequence
+    })
+  }
+
+  #[test]
+  fn cursor_style_default() {
+    let greeter = Greeter::default();
+
+    assert_eq!(cursor_sequence(get_cursor_style(&greeter, false)), None);
+    assert_eq!(cursor_sequence(get_cursor_style(&greeter, true)), None);
+  }
+
+  #[test]
+  fn cursor_style_steady() {
+    let mut greeter = Greeter::default();
+    greeter.steady_cursor = true;
+
+    assert_eq!(cursor_sequence(get_cursor_style(&greeter, false)), Some("\x1b[2 q".to_string()));
+    assert_eq!(cursor_sequence(get_cursor_style(&greeter, true)), None);
+  }
 
   // +-----------+
   // | Username: |
@@ -233,6 +361,41 @@ mod test {
     assert_eq!(get_height(&greeter), 8);
   }
 
+  // +-----------+
+  // |           |
+  // | Greeting  |
+  // |           |
+  // |           |
+  // |           |
+  // | Username: |
+  // |           |
+  // +-----------+
+  #[test]
+  fn test_container_height_username_greeting_spacing_three() {
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--container-padding", "1", "--greeting-prompt-spacing", "3"]).ok();
+    greeter.greeting = Some("Hello".into());
+    greeter.mode = Mode::Username;
+
+    assert_eq!(get_height(&greeter), 9);
+  }
+
+  // +-----------+
+  // |           |
+  // | Greeting  |
+  // | Username: |
+  // |           |
+  // +-----------+
+  #[test]
+  fn test_container_height_username_greeting_spacing_zero() {
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--container-padding", "1", "--greeting-prompt-spacing", "0"]).ok();
+    greeter.greeting = Some("Hello".into());
+    greeter.mode = Mode::Username;
+
+    assert_eq!(get_height(&greeter), 6);
+  }
+
   #[test]
   fn test_rect_bounds() {
     let mut greeter = Greeter::default();
@@ -282,6 +445,22 @@ mod test {
     assert_eq!(height, 3);
   }
 
+  #[test]
+  fn greeting_height_leading_blank_lines() {
+    let mut greeter = Greeter::default();
+    greeter.config = Greeter::options().parse(&["--width", "15", "--container-padding", "1"]).ok();
+    greeter.greeting = Some("\n\nHello\n".into());
+
+    let (_, trimmed_height) = get_greeting_height(&greeter, 1, 0);
+
+    greeter.greeting_trim = false;
+
+    let (_, height) = get_greeting_height(&greeter, 1, 0);
+
+    assert_eq!(trimmed_height, 2);
+    assert_eq!(height, 4);
+  }
+
   #[test]
   fn ansi_greeting_height_one_line() {
     let mut greeter = Greeter::default();
@@ -300,6 +479,41 @@ mod test {
     assert_eq!(height, 2);
   }
 
+  #[test]
+  fn input_scroll() {
+    let mut greeter = Greeter::default();
+
+    assert_eq!(get_input_scroll(&greeter, 5, 10), 0);
+    assert_eq!(get_input_scroll(&greeter, 9, 10), 0);
+    assert_eq!(get_input_scroll(&greeter, 10, 10), 1);
+    assert_eq!(get_input_scroll(&greeter, 30, 10), 21);
+
+    greeter.cursor_offset = -25;
+    assert_eq!(get_input_scroll(&greeter, 30, 10), 0);
+
+    greeter.cursor_offset = -50;
+    assert_eq!(get_input_scroll(&greeter, 30, 10), 0);
+
+    assert_eq!(get_visible_input("hello world", 6), "world");
+  }
+
+  #[test]
+  fn truncate_text() {
+    assert_eq!(truncate("uname", 10), "uname");
+    assert_eq!(truncate("uname", 5), "uname");
+    assert_eq!(truncate("uname -a", 5), "unam…");
+    assert_eq!(truncate("uname", 0), "");
+    assert_eq!(truncate("日本語のコマンド", 7), "日本語…");
+  }
+
+  #[test]
+  fn relative_time() {
+    assert_eq!(format_relative_time(chrono::Duration::seconds(30)), "just now");
+    assert_eq!(format_relative_time(chrono::Duration::minutes(5)), "5m ago");
+    assert_eq!(format_relative_time(chrono::Duration::minutes(150)), "2h ago");
+    assert_eq!(format_relative_time(chrono::Duration::days(2) + chrono::Duration::hours(3)), "2d ago");
+  }
+
   #[test]
   fn ansi_greeting_height_two_lines() {
     let mut greeter = Greeter::default();